@@ -1,3 +1,6 @@
+use chrono::{DateTime, NaiveDate, NaiveTime, SecondsFormat, Utc};
+use num_bigint::{BigInt, Sign};
+use rust_decimal::Decimal;
 use serde_derive::{Deserialize, Serialize};
 use std::fmt;
 
@@ -6,23 +9,45 @@ mod test;
 
 pub type Ident = String;
 
+/// Whether an identifier is an infix operator like `+`, rather than a name
+pub fn is_operator(ident: &str) -> bool {
+    !ident.starts_with(|c: char| c.is_alphanumeric() || c == '_')
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub enum Ty {
     Int,
+    Float,
+    BigInt,
+    Decimal,
+    Date,
+    Time,
+    Timestamp,
+    Bytes,
     Bool,
     Tuple(Vec<Ty>),
     Unit,
     String,
-    Record(Vec<(Ident, Ty)>),
+    /// A record type. With a row variable, the record may have other fields
+    /// as well, e.g. `{ name: String, ..r }`
+    Record(Vec<(Ident, Ty)>, Option<Ident>),
     Defined(Ident, Vec<Ty>),
     Var(Ident),
     Fun(Box<Ty>, Box<Ty>),
+    List(Box<Ty>),
 }
 
 impl fmt::Display for Ty {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Ty::Int => write!(f, "Int"),
+            Ty::Float => write!(f, "Float"),
+            Ty::BigInt => write!(f, "BigInt"),
+            Ty::Decimal => write!(f, "Decimal"),
+            Ty::Date => write!(f, "Date"),
+            Ty::Time => write!(f, "Time"),
+            Ty::Timestamp => write!(f, "Timestamp"),
+            Ty::Bytes => write!(f, "Bytes"),
             Ty::Bool => write!(f, "Bool"),
             Ty::Tuple(tys) => {
                 write!(f, "(")?;
@@ -37,7 +62,7 @@ impl fmt::Display for Ty {
             }
             Ty::Unit => write!(f, "()"),
             Ty::String => write!(f, "String"),
-            Ty::Record(recs) => {
+            Ty::Record(recs, row) => {
                 write!(f, "{{ ")?;
                 for (i, (ident, ty)) in recs.iter().enumerate() {
                     if i != 0 {
@@ -46,14 +71,28 @@ impl fmt::Display for Ty {
                     write!(f, "{}: {}", ident, ty)?;
                 }
 
+                if let Some(row) = row {
+                    if !recs.is_empty() {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "..{}", row)?;
+                }
+
                 write!(f, " }}")
             }
             Ty::Var(ident) => write!(f, "{}", ident),
-            Ty::Fun(lhs, rhs) => write!(f, "({} -> {})", lhs, rhs), // TODO: Handle parenthesis
+            Ty::List(ty) => write!(f, "[{}]", ty),
+            // Functions are right associative
+            Ty::Fun(lhs, rhs) if lhs.is_simple() => write!(f, "{} -> {}", lhs, rhs),
+            Ty::Fun(lhs, rhs) => write!(f, "({}) -> {}", lhs, rhs),
             Ty::Defined(name, args) => {
                 write!(f, "{}", name)?;
                 for arg in args {
-                    write!(f, " {}", arg)?;
+                    if arg.is_simple() {
+                        write!(f, " {}", arg)?;
+                    } else {
+                        write!(f, " ({})", arg)?;
+                    }
                 }
                 Ok(())
             }
@@ -61,6 +100,18 @@ impl fmt::Display for Ty {
     }
 }
 
+impl Ty {
+    /// Whether the type can be written without parentheses as the argument of
+    /// a type constructor or on the left of an arrow
+    fn is_simple(&self) -> bool {
+        match self {
+            Ty::Fun(..) => false,
+            Ty::Defined(_, args) => args.is_empty(),
+            _ => true,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct TableDefinition {
     pub ty: Ty,
@@ -70,9 +121,51 @@ pub struct TableDefinition {
 pub enum Pattern {
     Atom(Atom),
     Tuple(Vec<Pattern>),
-    Record(Vec<(Ident, Pattern)>),
+    /// The flag is set when the pattern ends in `..`, so the matched record
+    /// may have more fields than the ones listed.
+    Record(Vec<(Ident, Pattern)>, bool),
     Wildcard,
     Ident(Ident),
+    Constructor(Ident, Vec<Pattern>),
+    List(Vec<Pattern>),
+    Cons(Box<Pattern>, Box<Pattern>),
+    Or(Vec<Pattern>),
+    As(Box<Pattern>, Ident),
+}
+
+impl Pattern {
+    /// How tightly the pattern binds, from `as` patterns, which bind the
+    /// loosest, to patterns that can be constructor arguments
+    fn prec(&self) -> u8 {
+        match self {
+            Pattern::As(..) => 0,
+            Pattern::Or(..) => 1,
+            Pattern::Cons(..) => 2,
+            Pattern::Constructor(_, args) if !args.is_empty() => 3,
+            _ => 4,
+        }
+    }
+
+    /// Print the pattern, in parentheses if it binds looser than `prec`
+    fn fmt_prec(&self, f: &mut fmt::Formatter<'_>, prec: u8) -> fmt::Result {
+        if self.prec() < prec {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+
+    /// Print the pattern as the parameter of a lambda
+    fn fmt_param(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Atom(Atom::Unit)
+            | Pattern::Tuple(_)
+            | Pattern::Record(..)
+            | Pattern::Wildcard
+            | Pattern::Ident(_) => write!(f, "{}", self),
+            _ => write!(f, "({})", self),
+        }
+    }
 }
 
 impl fmt::Display for Pattern {
@@ -90,19 +183,63 @@ impl fmt::Display for Pattern {
 
                 write!(f, ")")
             }
-            Pattern::Record(recs) => {
+            Pattern::Record(recs, open) => {
                 write!(f, "{{ ")?;
                 for (i, (ident, pat)) in recs.iter().enumerate() {
                     if i != 0 {
                         write!(f, ", ")?;
                     }
-                    write!(f, "{} = {}", ident, pat)?;
+                    match pat {
+                        Pattern::Ident(name) if name == ident => write!(f, "{}", ident)?,
+                        _ => write!(f, "{} = {}", ident, pat)?,
+                    }
+                }
+                if *open {
+                    write!(f, ", ..")?;
                 }
 
                 write!(f, " }}")
             }
             Pattern::Wildcard => write!(f, "_"),
             Pattern::Ident(ident) => write!(f, "{}", ident),
+            Pattern::Constructor(name, pats) => {
+                write!(f, "{}", name)?;
+                for pat in pats {
+                    write!(f, " ")?;
+                    pat.fmt_prec(f, 4)?;
+                }
+                Ok(())
+            }
+            Pattern::List(pats) => {
+                write!(f, "[")?;
+                for (i, pat) in pats.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", pat)?;
+                }
+
+                write!(f, "]")
+            }
+            // `::` is right associative
+            Pattern::Cons(head, tail) => {
+                head.fmt_prec(f, 3)?;
+                write!(f, " :: ")?;
+                tail.fmt_prec(f, 2)
+            }
+            Pattern::Or(pats) => {
+                for (i, pat) in pats.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, " | ")?;
+                    }
+                    pat.fmt_prec(f, 2)?;
+                }
+                Ok(())
+            }
+            Pattern::As(pat, ident) => {
+                pat.fmt_prec(f, 1)?;
+                write!(f, " as {}", ident)
+            }
         }
     }
 }
@@ -112,36 +249,295 @@ pub enum Atom {
     Unit,
     Bool(bool),
     Int(i64),
+    Float(#[serde(with = "debug_float")] f64),
+    BigInt(BigInt),
+    Decimal(Decimal),
+    Date(NaiveDate),
+    Time(NaiveTime),
+    Timestamp(DateTime<Utc>),
+    Bytes(#[serde(with = "base64_bytes")] Vec<u8>),
     String(String),
 }
 
+/// Byte strings are sent over the wire as base64 rather than as a list of
+/// numbers.
+mod base64_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        base64::decode(String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Floats are sent over the wire as their debug representation, since
+/// serde_lexpr prints large and small floats with an exponent that it cannot
+/// read back.
+mod debug_float {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(x: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:?}", x))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
 impl fmt::Display for Atom {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Atom::Int(i) => write!(f, "{}", i),
-            Atom::Bool(b) => write!(f, "{}", b),
+            // The debug representation always has a decimal point or an
+            // exponent, so it reads back as a float.
+            Atom::Float(x) => write!(f, "{:?}", x),
+            Atom::BigInt(i) => write!(f, "{}n", i),
+            Atom::Decimal(d) => write!(f, "{}d", d),
+            Atom::Date(date) => write!(f, "@{}", date),
+            Atom::Time(time) => write!(f, "@{}", time),
+            Atom::Timestamp(ts) => write!(f, "@{}", fmt_timestamp(ts)),
+            Atom::Bytes(bytes) => write!(f, "hex\"{}\"", hex::encode(bytes)),
+            Atom::Bool(true) => write!(f, "True"),
+            Atom::Bool(false) => write!(f, "False"),
             Atom::Unit => write!(f, "()"),
-            Atom::String(s) => write!(f, "{:?}", s),
+            Atom::String(s) => write!(f, "{}", fmt_string(s)),
         }
     }
 }
 
+/// Quote a string, escaping it the same way that string literals are read
+pub fn fmt_string(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            '\u{8}' => res.push_str("\\b"),
+            '\u{c}' => res.push_str("\\f"),
+            c if c.is_control() => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
+    }
+
+    res.push('"');
+    res
+}
+
+/// ISO 8601 representation of a timestamp, with only as many fractional digits
+/// as needed.
+pub fn fmt_timestamp(ts: &DateTime<Utc>) -> String {
+    ts.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub enum Expr {
     Atom(Atom),
     Ident(Ident),
     Tuple(Vec<Expr>),
+    List(Vec<Expr>),
+    Cons(Box<Expr>, Box<Expr>),
     Record(Vec<(Ident, Expr)>),
-    Let(Vec<(Ident, Expr)>, Box<Expr>),
+    Let(Vec<(Pattern, Expr)>, Box<Expr>),
+    LetRec(Vec<(Ident, Expr)>, Box<Expr>),
     Apply(Box<Expr>, Box<Expr>),
-    Lambda(Ident, Box<Expr>),
-    Case(Box<Expr>, Vec<(Pattern, Expr)>),
+    /// A lambda, optionally annotated with the type of its parameter
+    Lambda(Ident, Option<Ty>, Box<Expr>),
+    Case(Box<Expr>, Vec<(Pattern, Option<Expr>, Expr)>),
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+    Field(Box<Expr>, Ident),
+    Update(Box<Expr>, Vec<(Ident, Expr)>),
+    /// An expression annotated with its type, `(e : T)`
+    Annot(Box<Expr>, Ty),
+}
+
+impl Expr {
+    /// Rebuild the expression with `ty` applied to its type annotations and
+    /// `pat` applied to the patterns of its let bindings and case arms,
+    /// stopping at the first error.
+    pub fn try_map<E>(
+        self,
+        ty: &mut impl FnMut(Ty) -> Result<Ty, E>,
+        pat: &mut impl FnMut(Pattern) -> Result<Pattern, E>,
+    ) -> Result<Expr, E> {
+        Ok(match self {
+            Expr::Atom(_) | Expr::Ident(_) => self,
+            Expr::Tuple(exprs) => Expr::Tuple(
+                exprs
+                    .into_iter()
+                    .map(|e| e.try_map(ty, pat))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expr::List(exprs) => Expr::List(
+                exprs
+                    .into_iter()
+                    .map(|e| e.try_map(ty, pat))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expr::Cons(head, tail) => Expr::Cons(
+                Box::new(head.try_map(ty, pat)?),
+                Box::new(tail.try_map(ty, pat)?),
+            ),
+            Expr::Record(recs) => Expr::Record(
+                recs.into_iter()
+                    .map(|(ident, e)| Ok((ident, e.try_map(ty, pat)?)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expr::Let(binds, body) => Expr::Let(
+                binds
+                    .into_iter()
+                    .map(|(p, e)| Ok((pat(p)?, e.try_map(ty, pat)?)))
+                    .collect::<Result<_, _>>()?,
+                Box::new(body.try_map(ty, pat)?),
+            ),
+            Expr::LetRec(binds, body) => Expr::LetRec(
+                binds
+                    .into_iter()
+                    .map(|(ident, e)| Ok((ident, e.try_map(ty, pat)?)))
+                    .collect::<Result<_, _>>()?,
+                Box::new(body.try_map(ty, pat)?),
+            ),
+            Expr::Apply(e1, e2) => Expr::Apply(
+                Box::new(e1.try_map(ty, pat)?),
+                Box::new(e2.try_map(ty, pat)?),
+            ),
+            Expr::Lambda(ident, param_ty, body) => Expr::Lambda(
+                ident,
+                param_ty.map(&mut *ty).transpose()?,
+                Box::new(body.try_map(ty, pat)?),
+            ),
+            Expr::Case(e, arms) => Expr::Case(
+                Box::new(e.try_map(ty, pat)?),
+                arms.into_iter()
+                    .map(|(p, guard, e)| {
+                        Ok((
+                            pat(p)?,
+                            guard.map(|guard| guard.try_map(ty, pat)).transpose()?,
+                            e.try_map(ty, pat)?,
+                        ))
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expr::If(cond, then, els) => Expr::If(
+                Box::new(cond.try_map(ty, pat)?),
+                Box::new(then.try_map(ty, pat)?),
+                Box::new(els.try_map(ty, pat)?),
+            ),
+            Expr::Field(e, field) => Expr::Field(Box::new(e.try_map(ty, pat)?), field),
+            Expr::Update(e, updates) => Expr::Update(
+                Box::new(e.try_map(ty, pat)?),
+                updates
+                    .into_iter()
+                    .map(|(ident, e)| Ok((ident, e.try_map(ty, pat)?)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expr::Annot(e, annot) => Expr::Annot(Box::new(e.try_map(ty, pat)?), ty(annot)?),
+        })
+    }
+}
+
+/// The parameter of a lambda taking a pattern parameter. It cannot be written
+/// in the source, so it never shadows user variables.
+pub const LAMBDA_ARG: &str = "#arg";
+
+/// The precedence of applications. Infix operators bind looser, and their
+/// precedences follow the parser.
+const APP_PREC: u8 = 5;
+
+/// The precedence of terms, which can be arguments of applications
+const TERM_PREC: u8 = 6;
+
+/// The precedence of `::`
+const CONS_PREC: u8 = 2;
+
+/// The precedence of an infix operator, if it can be written infix
+fn op_prec(op: &str) -> Option<u8> {
+    match op {
+        "==" | "!=" | "<" | "<=" | ">" | ">=" => Some(1),
+        "+" | "-" | "+." | "-." => Some(3),
+        "*" | "/" | "%" | "*." | "/." => Some(4),
+        _ => None,
+    }
+}
+
+/// Whether an atom is a negative number. Arguments cannot start with a `-`,
+/// as `f -1` is a subtraction.
+fn is_negative(atom: &Atom) -> bool {
+    match atom {
+        Atom::Int(i) => *i < 0,
+        Atom::Float(x) => x.is_sign_negative(),
+        Atom::BigInt(i) => i.sign() == Sign::Minus,
+        Atom::Decimal(d) => d.is_sign_negative(),
+        _ => false,
+    }
+}
+
+impl Expr {
+    /// An application of an infix operator, as desugared by the parser
+    fn as_infix(&self) -> Option<(&str, u8, &Expr, &Expr)> {
+        if let Expr::Apply(e1, rhs) = self {
+            if let Expr::Apply(op, lhs) = &**e1 {
+                if let Expr::Ident(op) = &**op {
+                    return op_prec(op).map(|prec| (op.as_str(), prec, &**lhs, &**rhs));
+                }
+            }
+        }
+        None
+    }
+
+    /// How tightly the expression binds. Lambdas and `if` extend as far to the
+    /// right as possible, so they bind the loosest.
+    fn prec(&self) -> u8 {
+        match self {
+            Expr::Lambda(..) | Expr::If(..) => 0,
+            Expr::Cons(..) => CONS_PREC,
+            Expr::Apply(..) => self.as_infix().map_or(APP_PREC, |(_, prec, _, _)| prec),
+            Expr::Atom(atom) if is_negative(atom) => APP_PREC,
+            _ => TERM_PREC,
+        }
+    }
+
+    /// Print the expression, in parentheses if it binds looser than `prec`
+    fn fmt_prec(&self, f: &mut fmt::Formatter<'_>, prec: u8) -> fmt::Result {
+        if self.prec() < prec {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+/// Print a let binding, writing an annotation on the bound expression as
+/// `let f : T = e`
+fn fmt_let(f: &mut fmt::Formatter<'_>, pat: &Pattern, expr: &Expr) -> fmt::Result {
+    match expr {
+        Expr::Annot(expr, ty) => write!(f, "let {} : {} = {}", pat, ty, expr),
+        _ => write!(f, "let {} = {}", pat, expr),
+    }
 }
 
 impl fmt::Display for Expr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((op, prec, lhs, rhs)) = self.as_infix() {
+            // All infix operators but `::` are left associative
+            lhs.fmt_prec(f, prec)?;
+            write!(f, " {} ", op)?;
+            return rhs.fmt_prec(f, prec + 1);
+        }
+
         match self {
             Expr::Atom(atom) => atom.fmt(f),
+            Expr::Ident(ident) if is_operator(ident) => write!(f, "({})", ident),
             Expr::Ident(ident) => write!(f, "{}", ident),
             Expr::Tuple(exprs) => {
                 write!(f, "(")?;
@@ -154,6 +550,22 @@ impl fmt::Display for Expr {
 
                 write!(f, ")")
             }
+            Expr::List(exprs) => {
+                write!(f, "[")?;
+                for (i, expr) in exprs.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", expr)?;
+                }
+
+                write!(f, "]")
+            }
+            Expr::Cons(head, tail) => {
+                head.fmt_prec(f, CONS_PREC + 1)?;
+                write!(f, " :: ")?;
+                tail.fmt_prec(f, CONS_PREC)
+            }
             Expr::Record(recs) => {
                 write!(f, "{{ ")?;
                 for (i, (ident, expr)) in recs.iter().enumerate() {
@@ -165,33 +577,111 @@ impl fmt::Display for Expr {
 
                 write!(f, " }}")
             }
+            // Several bindings are printed as nested lets, since the bound
+            // expression of one binding would take the next `let` as an
+            // argument
             Expr::Let(binds, e) => {
-                for (ident, expr) in binds.iter() {
-                    write!(f, "let {} = {} in ", ident, expr)?;
+                for (pat, expr) in binds.iter() {
+                    fmt_let(f, pat, expr)?;
+                    write!(f, " in ")?;
                 }
 
-                write!(f, "{} end", e)
+                write!(f, "{}", e)?;
+                for _ in binds {
+                    write!(f, " end")?;
+                }
+                Ok(())
             }
-            Expr::Apply(e1, e2) => write!(f, "({} {})", e1, e2), // TODO: Handle parenthesis
-            Expr::Lambda(ident, expr) => write!(f, "lambda {} -> {}", ident, expr),
+            Expr::LetRec(binds, e) => {
+                write!(f, "let rec ")?;
+                for (i, (ident, expr)) in binds.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, " and ")?;
+                    }
+                    write!(f, "{} = {}", ident, expr)?;
+                }
+
+                write!(f, " in {} end", e)
+            }
+            Expr::Apply(e1, e2) => {
+                e1.fmt_prec(f, APP_PREC)?;
+                write!(f, " ")?;
+                e2.fmt_prec(f, TERM_PREC)
+            }
+            Expr::Lambda(ident, None, expr) => match &**expr {
+                // A pattern parameter, as desugared by the parser
+                Expr::Let(binds, body)
+                    if ident == LAMBDA_ARG
+                        && binds.len() == 1
+                        && binds[0].1 == Expr::Ident(LAMBDA_ARG.to_string()) =>
+                {
+                    write!(f, "lambda ")?;
+                    binds[0].0.fmt_param(f)?;
+                    write!(f, " -> {}", body)
+                }
+                _ => write!(f, "lambda {} -> {}", ident, expr),
+            },
+            Expr::Lambda(ident, Some(ty), expr) => {
+                write!(f, "lambda ({} : {}) -> {}", ident, ty, expr)
+            }
+            Expr::Annot(expr, ty) => write!(f, "({} : {})", expr, ty),
             Expr::Case(expr, patexprs) => {
-                write!(f, "case {} of ", expr)?;
-                for (pat, expr) in patexprs.iter() {
-                    write!(f, "| {} => {} ", pat, expr)?;
+                write!(f, "case {} of", expr)?;
+                for (pat, guard, expr) in patexprs.iter() {
+                    write!(f, " | {}", pat)?;
+                    if let Some(guard) = guard {
+                        write!(f, " when {}", guard)?;
+                    }
+                    write!(f, " => {}", expr)?;
                 }
                 write!(f, " end")
             }
+            Expr::If(cond, then, els) => write!(f, "if {} then {} else {}", cond, then, els),
+            Expr::Field(e, field) => {
+                e.fmt_prec(f, TERM_PREC)?;
+                write!(f, ".{}", field)
+            }
+            Expr::Update(e, recs) => {
+                write!(f, "{{ {} with ", e)?;
+                for (i, (ident, expr)) in recs.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{} = {}", ident, expr)?;
+                }
+
+                write!(f, " }}")
+            }
         }
     }
 }
 
+/// A union type declaration: the name of the type, its type parameters and its
+/// constructors.
+pub type UnionDecl = (Ident, Vec<Ident>, Vec<(Ident, Vec<Ty>)>);
+
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum Statement {
     Create(Ident, TableDefinition),
     Insert(Ident, Expr),
     Select(Ident),
-    Let(Ident, Expr),
-    Union(Ident, Vec<Ident>, Vec<(Ident, Vec<Ty>)>),
+    Let(Pattern, Expr),
+    LetRec(Vec<(Ident, Expr)>),
+    /// A group of possibly mutually recursive union types, e.g. `type Tree =
+    /// Leaf | Node Forest and Forest = Nil | Cons Tree Forest`
+    Union(Vec<UnionDecl>),
+    /// A type alias, e.g. `type alias Email = String`, which stands for the
+    /// type it is defined as
+    Alias(Ident, Vec<Ident>, Ty),
+    /// A type with a single constructor wrapping a value of another type, e.g.
+    /// `newtype UserId = UserId Int`. It is a distinct type, but represented
+    /// by the wrapped value at runtime.
+    Newtype(Ident, Vec<Ident>, Ident, Ty),
+    /// Show the type of an expression without evaluating it
+    TypeOf(Expr),
+    /// Show the type of the rows of a table
+    TypeOfTable(Ident),
+    Builtins,
 }
 
 impl fmt::Display for Statement {
@@ -200,26 +690,70 @@ impl fmt::Display for Statement {
             Statement::Create(ident, def) => write!(f, "create table {} {}", ident, def.ty),
             Statement::Insert(ident, expr) => write!(f, "insert {} into {}", expr, ident),
             Statement::Select(ident) => write!(f, "select from {}", ident),
-            Statement::Let(ident, expr) => write!(f, "let {} = {}", ident, expr),
-            Statement::Union(ident, args, variants) => {
-                write!(f, "type {}", ident)?;
-                for arg in args {
-                    write!(f, " {}", arg)?;
-                }
-                write!(f, " =")?;
-                for (i, (name, types)) in variants.iter().enumerate() {
+            Statement::Builtins => write!(f, "builtins"),
+            Statement::TypeOf(expr) => write!(f, "typeof {}", expr),
+            Statement::TypeOfTable(ident) => write!(f, "typeof table {}", ident),
+            Statement::Let(pat, expr) => fmt_let(f, pat, expr),
+            Statement::LetRec(binds) => {
+                write!(f, "let rec ")?;
+                for (i, (ident, expr)) in binds.iter().enumerate() {
                     if i != 0 {
-                        write!(f, " |")?;
+                        write!(f, " and ")?;
                     }
-                    write!(f, " {}", name)?;
-                    for t in types {
-                        write!(f, " {}", t)?;
+                    write!(f, "{} = {}", ident, expr)?;
+                }
+                Ok(())
+            }
+            Statement::Union(decls) => {
+                write!(f, "type ")?;
+                for (i, (ident, args, variants)) in decls.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, " and ")?;
                     }
+                    fmt_union_decl(f, ident, args, variants)?;
                 }
                 Ok(())
             }
+            Statement::Alias(ident, args, ty) => {
+                write!(f, "type alias {}", ident)?;
+                for arg in args {
+                    write!(f, " {}", arg)?;
+                }
+                write!(f, " = {}", ty)
+            }
+            Statement::Newtype(ident, args, constructor, ty) => {
+                write!(f, "newtype ")?;
+                fmt_union_decl(f, ident, args, &[(constructor.clone(), vec![ty.clone()])])
+            }
+        }
+    }
+}
+
+fn fmt_union_decl(
+    f: &mut fmt::Formatter<'_>,
+    ident: &str,
+    args: &[Ident],
+    variants: &[(Ident, Vec<Ty>)],
+) -> fmt::Result {
+    write!(f, "{}", ident)?;
+    for arg in args {
+        write!(f, " {}", arg)?;
+    }
+    write!(f, " =")?;
+    for (i, (name, types)) in variants.iter().enumerate() {
+        if i != 0 {
+            write!(f, " |")?;
+        }
+        write!(f, " {}", name)?;
+        for t in types {
+            if t.is_simple() {
+                write!(f, " {}", t)?;
+            } else {
+                write!(f, " ({})", t)?;
+            }
         }
     }
+    Ok(())
 }
 
 pub type Statements = Vec<Statement>;
@@ -3,7 +3,7 @@ use super::*;
 #[test]
 fn display_expr() {
     assert_eq!(
-        "(foo (id x))".to_string(),
+        "foo (id x)".to_string(),
         format!(
             "{}",
             Expr::Apply(
@@ -17,7 +17,7 @@ fn display_expr() {
     );
 
     assert_eq!(
-        "((foo id) x)".to_string(),
+        "foo id x".to_string(),
         format!(
             "{}",
             Expr::Apply(
@@ -31,7 +31,7 @@ fn display_expr() {
     );
 
     assert_eq!(
-        "(foo \"Hello World!\")".to_string(),
+        "foo \"Hello World!\"".to_string(),
         format!(
             "{}",
             Expr::Apply(
@@ -45,7 +45,31 @@ fn display_expr() {
         "let foo = 42".to_string(),
         format!(
             "{}",
-            Statement::Let("foo".to_string(), Expr::Atom(Atom::Int(42)))
+            Statement::Let(Pattern::Ident("foo".to_string()), Expr::Atom(Atom::Int(42)))
         )
     );
 }
+
+#[test]
+fn bytes_wire_encoding() {
+    let atom = Atom::Bytes(vec![0, 1, 254, 255]);
+    let encoded = serde_lexpr::to_string(&atom).unwrap();
+
+    assert_eq!("(Bytes . \"AAH+/w==\")", encoded);
+    assert_eq!(atom, serde_lexpr::from_str(&encoded).unwrap());
+}
+
+#[test]
+fn float_wire_encoding() {
+    for x in &[1e300, 1e20, 5e-324, 1e-7, -1.5, 0.0, f64::INFINITY] {
+        let atom = Atom::Float(*x);
+        let encoded = serde_lexpr::to_string(&atom).unwrap();
+
+        assert_eq!(atom, serde_lexpr::from_str(&encoded).unwrap());
+    }
+
+    assert_eq!(
+        "(Float . \"1e300\")",
+        serde_lexpr::to_string(&Atom::Float(1e300)).unwrap()
+    );
+}
@@ -1,13 +1,12 @@
 use anyhow::{anyhow, Error, Result};
+use pdb::cli::expand_shortcuts;
 use pdb::parse::parse;
+use pdb::wire::{self, Reply};
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
-use serde_lexpr::to_string;
 use structopt::StructOpt;
 use tokio::io::AsyncBufRead;
-use tokio::io::AsyncBufReadExt;
 use tokio::io::AsyncWrite;
-use tokio::io::AsyncWriteExt;
 use tokio::io::BufReader;
 use tokio::io::BufWriter;
 use tokio::net::TcpStream;
@@ -49,18 +48,15 @@ async fn start(_config: Config, mut stream: impl AsyncWrite + AsyncBufRead + Unp
         let readline = rl.readline(PROMPT);
         match readline {
             Ok(line) => {
-                match parse(&line) {
+                match parse(&expand_shortcuts(&line)) {
                     Ok(stm) => {
-                        let mut sexp = to_string(&stm)?;
-                        sexp.push('\n');
-                        stream.write_all(sexp.as_bytes()).await?;
-                        stream.flush().await?;
+                        wire::send(&mut stream, &stm).await?;
 
-                        let mut buffer = String::new();
-
-                        let _ = stream.read_line(&mut buffer).await?;
-
-                        println!("{}", buffer);
+                        match wire::receive::<Reply>(&mut stream).await? {
+                            Some(Ok(msg)) => println!("{}", msg),
+                            Some(Err(e)) => println!("Err: {}", e),
+                            None => return Err(anyhow!("Connection closed by the server")),
+                        }
                     }
                     Err(e) => {
                         println!("No parse: {}\n", e);
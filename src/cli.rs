@@ -7,13 +7,24 @@ use std::sync::mpsc::{channel, Sender};
 
 const PROMPT: &str = ">> ";
 
+/// Expand the REPL shortcuts in a line of input: `:type e` stands for `typeof
+/// e`
+pub fn expand_shortcuts(line: &str) -> String {
+    match line.trim_start().strip_prefix(":type") {
+        Some(rest) if rest.is_empty() || rest.starts_with(char::is_whitespace) => {
+            format!("typeof{}", rest)
+        }
+        _ => line.to_string(),
+    }
+}
+
 pub fn start(tx: Sender<(Statement, Sender<Result<String>>)>) -> Result<()> {
     let mut rl = Editor::<()>::new();
     loop {
         let readline = rl.readline(PROMPT);
         match readline {
             Ok(line) => {
-                match parse(&line) {
+                match parse(&expand_shortcuts(&line)) {
                     Ok(ast) => {
                         let (tx2, rx2) = channel();
 
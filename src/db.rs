@@ -1,12 +1,17 @@
-use crate::ast::{Ident, Statement, TableDefinition, Ty};
+use crate::ast::{Expr, Ident, Statement, TableDefinition, Ty, UnionDecl};
 use crate::environment::Environment;
-use crate::eval::eval;
+use crate::eval::{
+    bind_rec, constructor, erase_newtype_pat, erase_newtypes, eval, match_pat, newtype_constructor,
+};
 use crate::name_source::NameSource;
 use crate::object::Object;
+use crate::prelude;
 use crate::ty;
 use anyhow::{anyhow, Context, Result};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
 
 type Tables = Vec<(Ident, TableDefinition, Vec<Object>)>;
 
@@ -14,12 +19,98 @@ pub struct Env {
     ty_env: ty::Env,
     env: Environment,
     tables: Tables,
+    types: ty::TypeDefs,
+    aliases: ty::Aliases,
     constructors: HashMap<Ident, (Vec<Ty>, Ident)>,
+    /// The constructors of newtypes, which are erased before evaluation
+    newtypes: HashSet<Ident>,
+}
+
+/// Declare a group of (possibly mutually recursive) union types, making their
+/// constructors available as values and in patterns.
+fn declare_unions(env: &mut Env, decls: Vec<UnionDecl>) -> Result<String> {
+    let types = ty::check_unions(&env.types, &decls).map_err(|e| anyhow!("{}", e))?;
+
+    for (_, _, variants) in &decls {
+        for (variant_name, _) in variants {
+            if env.constructors.contains_key(variant_name) {
+                return Err(anyhow!(
+                    "Constructor {} has already been declared\n",
+                    variant_name
+                ));
+            }
+        }
+    }
+
+    env.types = types;
+
+    for decl in &decls {
+        for (variant_name, scheme) in ty::constructor_schemes(decl) {
+            env.ty_env.insert(variant_name, scheme);
+        }
+
+        let (name, _, variants) = decl;
+        for (variant_name, tyargs) in variants {
+            env.env = env
+                .env
+                .insert(variant_name, constructor(variant_name, tyargs.len()));
+            env.constructors
+                .insert(variant_name.clone(), (tyargs.clone(), name.clone()));
+        }
+    }
+
+    Ok(String::from("Ok\n"))
+}
+
+/// Replace the type aliases in the types and type annotations of a statement
+/// by the types they stand for
+fn expand_aliases(env: &Env, stm: Statement) -> Result<Statement> {
+    let expand = |ty: &Ty| ty::expand_aliases(&env.aliases, ty).map_err(|e| anyhow!("{}", e));
+    let expand_expr = |e: Expr| e.try_map(&mut |ty| expand(&ty), &mut Ok);
+    let expand_decl = |(name, params, variants): UnionDecl| -> Result<UnionDecl> {
+        let variants = variants
+            .into_iter()
+            .map(|(con, args)| Ok((con, args.iter().map(expand).collect::<Result<_>>()?)))
+            .collect::<Result<_>>()?;
+        Ok((name, params, variants))
+    };
+
+    Ok(match stm {
+        Statement::Create(ident, def) => Statement::Create(
+            ident,
+            TableDefinition {
+                ty: expand(&def.ty)?,
+            },
+        ),
+        Statement::Insert(ident, e) => Statement::Insert(ident, expand_expr(e)?),
+        Statement::Let(pat, e) => Statement::Let(pat, expand_expr(e)?),
+        Statement::LetRec(binds) => Statement::LetRec(
+            binds
+                .into_iter()
+                .map(|(ident, e)| Ok((ident, expand_expr(e)?)))
+                .collect::<Result<_>>()?,
+        ),
+        Statement::Union(decls) => {
+            Statement::Union(decls.into_iter().map(expand_decl).collect::<Result<_>>()?)
+        }
+        Statement::Newtype(name, params, con, ty) => {
+            Statement::Newtype(name, params, con, expand(&ty)?)
+        }
+        // The type of an alias is expanded when it is checked
+        Statement::TypeOf(e) => Statement::TypeOf(expand_expr(e)?),
+        stm @ (Statement::Alias(..)
+        | Statement::Select(_)
+        | Statement::TypeOfTable(_)
+        | Statement::Builtins) => stm,
+    })
 }
 
 pub fn eval_stm(env: &mut Env, stm: Statement) -> Result<String> {
-    match stm {
+    match expand_aliases(env, stm)? {
         Statement::Create(ident, def) => {
+            ty::check_table(&env.types, &env.ty_env, &def.ty)
+                .map_err(|e| anyhow!("Could not create table {}: {}", ident, e))?;
+
             env.tables.push((ident, def, Vec::new()));
             Ok(String::from("Created\n"))
         }
@@ -34,22 +125,28 @@ pub fn eval_stm(env: &mut Env, stm: Statement) -> Result<String> {
                     &mut HashMap::new(),
                     &mut NameSource::new(),
                     &env.ty_env,
+                    &env.newtypes,
                     &expr,
                 )
                 .map_err(|e| anyhow!("{}", e))?;
 
-                if ty::unify(std::iter::once((ty, def.ty.clone()))).all(|x| x.is_ok()) {
-                    let result = eval(&env.env, expr)?;
-                    objs.push(result);
-                    Ok(String::from("Inserted 1\n"))
-                } else {
-                    Err(anyhow!(
-                        "Could not insert {:?} into table {:?} with definition {:?}\n",
+                if let Some(e) =
+                    ty::unify(std::iter::once((def.ty.clone(), ty.clone()))).find_map(|x| x.err())
+                {
+                    return Err(anyhow!(
+                        "Could not insert into table {}: {}\n  `{}` has type {}\n  table {} has type {}\n",
+                        ident,
+                        e,
                         expr,
+                        ty,
                         ident,
                         &def.ty
-                    ))
+                    ));
                 }
+
+                let result = eval(&env.env, erase_newtypes(&env.newtypes, expr))?;
+                objs.push(result);
+                Ok(String::from("Inserted 1\n"))
             } else {
                 Err(anyhow!("No such table\n"))
             }
@@ -61,45 +158,149 @@ pub fn eval_stm(env: &mut Env, stm: Statement) -> Result<String> {
                 Err(anyhow!("No such table\n"))
             }
         }
-        Statement::Let(ident, expr) => {
-            // infer type of expr and try to unify with def.ty
-            let ty = ty::infer(
+        Statement::Let(pat, expr) => {
+            let schemes = ty::infer_let(
                 &mut HashMap::new(),
                 &mut NameSource::new(),
                 &env.ty_env,
+                &env.newtypes,
+                &pat,
                 &expr,
             )
-            .map_err(|e| anyhow!("{}", e))
-            .unwrap();
-
-            let scheme = ty::generalize(&env.ty_env, ty.clone());
+            .map_err(|e| anyhow!("{}", e))?;
 
-            env.ty_env.insert(ident.clone(), scheme);
+            let obj = eval(&env.env, erase_newtypes(&env.newtypes, expr))?;
+            let pat = erase_newtype_pat(&env.newtypes, pat);
 
-            let obj = eval(&env.env, expr)?;
+            env.env = match_pat(&env.env, &pat, &obj)
+                .ok_or_else(|| anyhow!("Pattern {} did not match {}", pat, obj))?;
 
-            env.env = env.env.insert(&ident, obj);
+            let mut res = String::new();
+            for (ident, scheme) in schemes {
+                res.push_str(&format!(
+                    "{}: {}\n",
+                    ident,
+                    ty::fmt_scheme(&ty::rename_scheme(&scheme))
+                ));
+                env.ty_env.insert(ident, scheme);
+            }
 
-            Ok(format!("{}: {}\n", ident, ty))
+            Ok(res)
         }
-        Statement::Union(name, _args, variants) => {
-            for (variant_name, tyargs) in variants {
-                env.constructors
-                    .insert(variant_name.clone(), (tyargs.clone(), name.clone()));
+        Statement::LetRec(binds) => {
+            let mut global_sub = HashMap::new();
+            let schemes = ty::infer_rec(
+                &mut global_sub,
+                &mut NameSource::new(),
+                &env.ty_env,
+                &env.newtypes,
+                &binds,
+            )
+            .map_err(|e| anyhow!("{}", e))?;
+
+            let binds = binds
+                .into_iter()
+                .map(|(ident, e)| (ident, erase_newtypes(&env.newtypes, e)))
+                .collect();
+            env.env = bind_rec(&env.env, Rc::new(binds))?;
+
+            let mut res = String::new();
+            for (ident, scheme) in schemes {
+                res.push_str(&format!(
+                    "{}: {}\n",
+                    ident,
+                    ty::fmt_scheme(&ty::rename_scheme(&scheme))
+                ));
+                env.ty_env.insert(ident, scheme);
             }
+
+            Ok(res)
+        }
+        Statement::Union(decls) => declare_unions(env, decls),
+        Statement::Alias(name, params, ty) => {
+            let ty = ty::check_alias(&env.types, &env.aliases, &name, &params, &ty)
+                .map_err(|e| anyhow!("{}", e))?;
+
+            env.types.insert(name.clone(), params.clone());
+            env.aliases.insert(name, (params, ty));
+
             Ok(String::from("Ok\n"))
         }
+        Statement::Newtype(name, params, con, ty) => {
+            let res = declare_unions(env, vec![(name, params, vec![(con.clone(), vec![ty])])])?;
+
+            // Newtypes are type checked like other union types, but their
+            // values are the values they wrap
+            env.env = env.env.insert(&con, newtype_constructor());
+            env.newtypes.insert(con);
+
+            Ok(res)
+        }
+        Statement::TypeOf(expr) => {
+            let scheme = ty::infer_scheme(
+                &mut HashMap::new(),
+                &mut NameSource::new(),
+                &env.ty_env,
+                &env.newtypes,
+                &expr,
+            )
+            .map_err(|e| anyhow!("{}", e))?;
+
+            Ok(format!(
+                "{}: {}\n",
+                expr,
+                ty::fmt_scheme(&ty::rename_scheme(&scheme))
+            ))
+        }
+        Statement::TypeOfTable(ident) => {
+            let (_, def, _) = env
+                .tables
+                .iter()
+                .find(|(ident2, _, _)| ident2 == &ident)
+                .ok_or_else(|| anyhow!("No such table\n"))?;
+
+            Ok(format!("{}: {}\n", ident, def.ty))
+        }
+        Statement::Builtins => Ok(prelude::builtins()
+            .into_iter()
+            .map(|(name, scheme, _)| {
+                format!("{}: {}\n", Expr::Ident(name), ty::fmt_scheme(&scheme))
+            })
+            .collect()),
     }
 }
 
+/// The stack size of the db thread. Evaluation and type inference recurse
+/// over the statements they are given, up to `eval::MAX_DEPTH` and
+/// `ty::MAX_DEPTH` levels deep, which needs more than the default stack in
+/// debug builds.
+pub const STACK_SIZE: usize = 256 << 20;
+
+/// Run `start` on a new thread with a large enough stack
+pub fn spawn(rx: Receiver<(Statement, Sender<Result<String>>)>) -> JoinHandle<Result<()>> {
+    thread::Builder::new()
+        .name("db".to_string())
+        .stack_size(STACK_SIZE)
+        .spawn(|| start(rx))
+        .expect("Could not spawn the db thread")
+}
+
 pub fn start(rx: Receiver<(Statement, Sender<Result<String>>)>) -> Result<()> {
     let mut env = Env {
         ty_env: HashMap::new(),
         env: Environment::new(),
         tables: Vec::new(),
+        types: HashMap::new(),
+        aliases: HashMap::new(),
         constructors: HashMap::new(),
+        newtypes: HashSet::new(),
     };
 
+    for (name, scheme, obj) in prelude::builtins() {
+        env.ty_env.insert(name.clone(), scheme);
+        env.env = env.env.insert(&name, obj);
+    }
+
     loop {
         let (stm, tx) = match rx.recv() {
             Ok(x) => x,
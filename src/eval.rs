@@ -3,6 +3,9 @@ use crate::environment::Environment;
 use crate::object::Object;
 use anyhow::anyhow;
 use anyhow::Result;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::convert::Infallible;
 use std::rc::Rc;
 
 #[cfg(test)]
@@ -14,22 +17,79 @@ pub fn eval_atom(_env: &Environment, atom: &Atom) -> Result<Object> {
             let res = Object::Int(*i);
             Ok(res)
         }
+        Atom::Float(x) => Ok(Object::Float(*x)),
+        Atom::BigInt(i) => Ok(Object::BigInt(i.clone())),
+        Atom::Decimal(d) => Ok(Object::Decimal(*d)),
+        Atom::Date(date) => Ok(Object::Date(*date)),
+        Atom::Time(time) => Ok(Object::Time(*time)),
+        Atom::Timestamp(ts) => Ok(Object::Timestamp(*ts)),
+        Atom::Bytes(bytes) => Ok(Object::Bytes(bytes.clone())),
         Atom::Bool(b) => Ok(Object::Bool(*b)),
         Atom::Unit => Ok(Object::Unit),
         Atom::String(s) => Ok(Object::String(s.clone())),
     }
 }
 
+/// The maximum number of nested calls to `eval`. Deeper recursion in the
+/// evaluated program fails with an error rather than overflowing the stack.
+pub const MAX_DEPTH: usize = 10_000;
+
+thread_local! {
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// A nested call to `eval`, counted for as long as the guard lives
+struct DepthGuard;
+
+impl DepthGuard {
+    fn enter() -> Result<Self> {
+        DEPTH.with(|depth| {
+            if depth.get() >= MAX_DEPTH {
+                return Err(anyhow!(
+                    "Evaluation is nested more than {} calls deep",
+                    MAX_DEPTH
+                ));
+            }
+            depth.set(depth.get() + 1);
+            Ok(DepthGuard)
+        })
+    }
+}
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
 pub fn eval(env: &Environment, expr: Expr) -> Result<Object> {
+    let _depth = DepthGuard::enter()?;
+
     match expr {
         Expr::Atom(atom) => Ok(eval_atom(env, &atom)?),
-        Expr::Ident(ident) => env.lookup(&ident).map(|x| x.clone()),
+        Expr::Ident(ident) => env.lookup(&ident).cloned(),
         Expr::Tuple(exprs) => Ok(Object::Tuple(
             exprs
                 .into_iter()
                 .map(|e| eval(env, e))
                 .collect::<Result<Vec<_>>>()?,
         )),
+        Expr::List(exprs) => Ok(Object::List(
+            exprs
+                .into_iter()
+                .map(|e| eval(env, e))
+                .collect::<Result<Vec<_>>>()?,
+        )),
+        Expr::Cons(head, tail) => {
+            let head = eval(env, *head)?;
+            match eval(env, *tail)? {
+                Object::List(mut objs) => {
+                    objs.insert(0, head);
+                    Ok(Object::List(objs))
+                }
+                other => unreachable!("{}", other),
+            }
+        }
         Expr::Record(xs) => {
             let mut res = xs
                 .into_iter()
@@ -43,13 +103,15 @@ pub fn eval(env: &Environment, expr: Expr) -> Result<Object> {
                 .into_iter()
                 .try_fold::<Environment, _, Result<Environment>>(
                     env.clone(),
-                    |env, (ident, e_inner)| {
+                    |env, (pat, e_inner)| {
                         let obj = eval(&env, e_inner)?;
-                        Ok(env.insert(&ident, obj))
+                        match_pat(&env, &pat, &obj)
+                            .ok_or_else(|| anyhow!("Pattern {} did not match {}", pat, obj))
                     },
                 )?;
             eval(&env_, *e)
         }
+        Expr::LetRec(binds, e) => eval(&bind_rec(env, Rc::new(binds))?, *e),
         Expr::Apply(e1, e2) => {
             let obj = eval(env, *e2)?;
             match eval(env, *e1)? {
@@ -57,7 +119,7 @@ pub fn eval(env: &Environment, expr: Expr) -> Result<Object> {
                 other => unreachable!("{}", other),
             }
         }
-        Expr::Lambda(ident, e) => {
+        Expr::Lambda(ident, _, e) => {
             let env = env.clone();
             Ok(Object::Closure(Rc::new(move |obj| {
                 eval(&env.insert(&ident, obj), *e.clone())
@@ -66,23 +128,154 @@ pub fn eval(env: &Environment, expr: Expr) -> Result<Object> {
         Expr::Case(expr, matches) => {
             let obj = eval(env, *expr)?;
 
-            for (pat, e) in matches {
+            for (pat, guard, e) in matches {
                 // See if obj matches obj, returning an updated environment
                 if let Some(env) = match_pat(env, &pat, &obj) {
-                    return eval(&env, e);
+                    let guarded = match guard {
+                        Some(guard) => matches!(eval(&env, guard)?, Object::Bool(true)),
+                        None => true,
+                    };
+
+                    if guarded {
+                        return eval(&env, e);
+                    }
                 }
             }
 
             Err(anyhow!("No match found for case!"))
         }
+        Expr::Annot(e, _) => eval(env, *e),
+        Expr::If(cond, then, els) => match eval(env, *cond)? {
+            Object::Bool(true) => eval(env, *then),
+            Object::Bool(false) => eval(env, *els),
+            other => unreachable!("{}", other),
+        },
+        Expr::Field(e, field) => match eval(env, *e)? {
+            Object::Record(recs) => recs
+                .into_iter()
+                .find(|(ident, _)| ident == &field)
+                .map(|(_, obj)| obj)
+                .ok_or_else(|| anyhow!("No field {} in record", field)),
+            other => unreachable!("{}", other),
+        },
+        Expr::Update(e, updates) => match eval(env, *e)? {
+            Object::Record(mut recs) => {
+                for (field, e_inner) in updates {
+                    let obj = eval(env, e_inner)?;
+                    match recs.iter_mut().find(|(ident, _)| ident == &field) {
+                        Some((_, old)) => *old = obj,
+                        None => return Err(anyhow!("No field {} in record", field)),
+                    }
+                }
+                Ok(Object::Record(recs))
+            }
+            other => unreachable!("{}", other),
+        },
+    }
+}
+
+/// The value of a constructor taking `arity` arguments: either the constructed
+/// value itself, or a curried function collecting the arguments.
+pub fn constructor(name: &str, arity: usize) -> Object {
+    fn collect(name: Ident, arity: usize, args: Vec<Object>) -> Object {
+        if args.len() == arity {
+            Object::Variant(name, args)
+        } else {
+            Object::Closure(Rc::new(move |obj| {
+                let mut args = args.clone();
+                args.push(obj);
+                Ok(collect(name.clone(), arity, args))
+            }))
+        }
     }
+
+    collect(name.to_string(), arity, Vec::new())
+}
+
+/// The value of a newtype constructor. A newtype is represented by the value it
+/// wraps, so its constructor is the identity function.
+pub fn newtype_constructor() -> Object {
+    Object::Closure(Rc::new(Ok))
 }
 
-fn match_pat(env: &Environment, pat: &Pattern, obj: &Object) -> Option<Environment> {
+/// Remove the patterns of the newtype constructors in `newtypes` from `expr`,
+/// since a newtype is represented by the value it wraps. The expression must
+/// have been type checked with the newtypes as ordinary constructors.
+pub fn erase_newtypes(newtypes: &HashSet<Ident>, expr: Expr) -> Expr {
+    let Ok(expr) = expr.try_map(&mut Ok::<_, Infallible>, &mut |pat| {
+        Ok(erase_newtype_pat(newtypes, pat))
+    });
+    expr
+}
+
+pub fn erase_newtype_pat(newtypes: &HashSet<Ident>, pat: Pattern) -> Pattern {
+    let erase_all = |pats: Vec<Pattern>| {
+        pats.into_iter()
+            .map(|pat| erase_newtype_pat(newtypes, pat))
+            .collect()
+    };
+
+    match pat {
+        Pattern::Constructor(name, mut pats) if newtypes.contains(&name) && pats.len() == 1 => {
+            erase_newtype_pat(newtypes, pats.remove(0))
+        }
+        Pattern::Constructor(name, pats) => Pattern::Constructor(name, erase_all(pats)),
+        Pattern::Tuple(pats) => Pattern::Tuple(erase_all(pats)),
+        Pattern::List(pats) => Pattern::List(erase_all(pats)),
+        Pattern::Or(pats) => Pattern::Or(erase_all(pats)),
+        Pattern::Record(recs, is_open) => Pattern::Record(
+            recs.into_iter()
+                .map(|(ident, pat)| (ident, erase_newtype_pat(newtypes, pat)))
+                .collect(),
+            is_open,
+        ),
+        Pattern::Cons(head, tail) => Pattern::Cons(
+            Box::new(erase_newtype_pat(newtypes, *head)),
+            Box::new(erase_newtype_pat(newtypes, *tail)),
+        ),
+        Pattern::As(pat, ident) => Pattern::As(Box::new(erase_newtype_pat(newtypes, *pat)), ident),
+        Pattern::Atom(_) | Pattern::Wildcard | Pattern::Ident(_) => pat,
+    }
+}
+
+/// Extend `env` with a group of mutually recursive functions. Instead of
+/// mutating the environment after the fact, each closure ties the knot by
+/// rebuilding the recursive environment whenever it is called, so the
+/// functions can refer to themselves and each other.
+pub fn bind_rec(env: &Environment, binds: Rc<Vec<(Ident, Expr)>>) -> Result<Environment> {
+    let mut res = env.clone();
+
+    for (i, (ident, e)) in binds.iter().enumerate() {
+        if !matches!(e, Expr::Lambda(..)) {
+            return Err(anyhow!("Recursive binding {} must be a lambda", ident));
+        }
+
+        let env = env.clone();
+        let binds = binds.clone();
+        res = res.insert(
+            ident,
+            Object::Closure(Rc::new(move |obj| {
+                let rec_env = bind_rec(&env, binds.clone())?;
+                match &binds[i].1 {
+                    Expr::Lambda(param, _, body) => {
+                        eval(&rec_env.insert(param, obj), *body.clone())
+                    }
+                    other => unreachable!("{}", other),
+                }
+            })),
+        );
+    }
+
+    Ok(res)
+}
+
+/// Match `obj` against `pat`, returning `env` extended with the variables bound
+/// by the pattern, or `None` if it does not match.
+pub fn match_pat(env: &Environment, pat: &Pattern, obj: &Object) -> Option<Environment> {
     match (pat, obj) {
-        (Pattern::Ident(ident), _) => Some(env.insert(&ident, obj.clone())),
+        (Pattern::Ident(ident), _) => Some(env.insert(ident, obj.clone())),
         (Pattern::Atom(atom), _) => {
-            if &eval_atom(&env, atom).ok()? == obj {
+            if &eval_atom(env, atom).ok()? == obj {
                 Some(env.clone())
             } else {
                 None
@@ -92,19 +285,50 @@ fn match_pat(env: &Environment, pat: &Pattern, obj: &Object) -> Option<Environme
             // I assume that they match, since we've already type checked
             let mut env = env.clone();
             for (pat, obj) in pats.iter().zip(objs.iter()) {
-                env = match_pat(&env, pat, &obj)?
+                env = match_pat(&env, pat, obj)?
             }
             Some(env)
         }
-        (Pattern::Record(recs), Object::Record(obj_recs)) => {
-            // I assume that they match, since we've already type checked
+        (Pattern::Record(recs, _), Object::Record(obj_recs)) => {
+            // The pattern may list only some of the fields, so look them up by
+            // name
             let mut env = env.clone();
-            for ((_, pat), (_, obj)) in recs.iter().zip(obj_recs.iter()) {
-                env = match_pat(&env, pat, &obj)?
+            for (ident, pat) in recs {
+                let (_, obj) = obj_recs.iter().find(|(field, _)| field == ident)?;
+                env = match_pat(&env, pat, obj)?
             }
             Some(env)
         }
         (Pattern::Wildcard, _) => Some(env.clone()),
+        (Pattern::List(pats), Object::List(objs)) => {
+            if pats.len() != objs.len() {
+                return None;
+            }
+
+            let mut env = env.clone();
+            for (pat, obj) in pats.iter().zip(objs.iter()) {
+                env = match_pat(&env, pat, obj)?
+            }
+            Some(env)
+        }
+        (Pattern::Cons(head, tail), Object::List(objs)) => {
+            let (obj, rest) = objs.split_first()?;
+            let env = match_pat(env, head, obj)?;
+            match_pat(&env, tail, &Object::List(rest.to_vec()))
+        }
+        (Pattern::Constructor(name, pats), Object::Variant(obj_name, objs)) => {
+            if name != obj_name {
+                return None;
+            }
+
+            let mut env = env.clone();
+            for (pat, obj) in pats.iter().zip(objs.iter()) {
+                env = match_pat(&env, pat, obj)?
+            }
+            Some(env)
+        }
+        (Pattern::Or(pats), _) => pats.iter().find_map(|pat| match_pat(env, pat, obj)),
+        (Pattern::As(pat, ident), _) => Some(match_pat(env, pat, obj)?.insert(ident, obj.clone())),
         _ => None,
     }
 }
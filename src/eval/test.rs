@@ -22,6 +22,53 @@ fn eval_bool() {
     );
 }
 
+#[test]
+fn eval_float() {
+    assert_eq!(
+        "1.0",
+        format!(
+            "{}",
+            eval(&Environment::new(), Expr::Atom(Atom::Float(1.0))).unwrap()
+        )
+    );
+
+    assert_eq!(
+        "42",
+        format!(
+            "{}",
+            parse_and_eval("case 0.5 of 1.0 => 0 | 0.5 => 42 end").unwrap()
+        )
+    );
+}
+
+#[test]
+fn eval_bytes() {
+    assert_eq!(
+        "hex\"cafe\"",
+        format!("{}", parse_and_eval("base64\"yv4=\"").unwrap())
+    );
+
+    assert!(parse_and_eval("hex\"cafe\"").unwrap() == parse_and_eval("base64\"yv4=\"").unwrap());
+
+    assert!(parse_and_eval("hex\"cafe\"").unwrap() != parse_and_eval("hex\"caff\"").unwrap());
+
+    assert_eq!(
+        "1",
+        format!(
+            "{}",
+            parse_and_eval("case hex\"00\" of hex\"\" => 0 | hex\"00\" => 1 end").unwrap()
+        )
+    );
+}
+
+#[test]
+fn eval_string() {
+    assert_eq!(
+        r#""a\"b\nc""#,
+        format!("{}", parse_and_eval(r#""a\"b\u000ac""#).unwrap())
+    );
+}
+
 #[test]
 fn eval_tuple() {
     assert_eq!(
@@ -93,6 +140,59 @@ fn eval_id() {
     );
 }
 
+#[test]
+fn eval_let_pattern() {
+    assert_eq!(
+        "(2, 1)",
+        format!(
+            "{}",
+            parse_and_eval("let (a, b) = (1, 2) in (b, a) end").unwrap()
+        )
+    );
+
+    assert_eq!(
+        "(3, (1, 2))",
+        format!(
+            "{}",
+            parse_and_eval("let { y = py, .. } as p = { x = (1, 2), y = 3 } in (py, p.x) end")
+                .unwrap()
+        )
+    );
+}
+
+#[test]
+fn eval_annotations() {
+    assert_eq!(
+        "(42, true)",
+        format!(
+            "{}",
+            parse_and_eval(
+                "let f : Int -> (Int, Bool) = lambda (x : Int) -> ((x : Int), True) in f 42 end"
+            )
+            .unwrap()
+        )
+    );
+}
+
+#[test]
+fn eval_multi_argument_lambda() {
+    assert_eq!(
+        "(2, 3, 1)",
+        format!(
+            "{}",
+            parse_and_eval("(lambda (a, b) c -> (b, c, a)) (1, 2) 3").unwrap()
+        )
+    );
+
+    assert_eq!(
+        "42",
+        format!(
+            "{}",
+            parse_and_eval("let first x _ = x in first 42 43 end").unwrap()
+        )
+    );
+}
+
 #[test]
 fn eval_first() {
     assert_eq!(
@@ -136,4 +236,235 @@ fn eval_case() {
             parse_and_eval("case { x = 42, y = True } of { x = i, y = _ } => i end").unwrap()
         )
     );
+
+    assert_eq!(
+        "42",
+        format!(
+            "{}",
+            parse_and_eval("case 2 of 0 | 1 => 0 | 2 | 3 => 42 end").unwrap()
+        )
+    );
+
+    assert_eq!(
+        "((1, 2), 2)",
+        format!(
+            "{}",
+            parse_and_eval("case (1, 2) of (_, y) as p => (p, y) end").unwrap()
+        )
+    );
+
+    assert_eq!(
+        "0",
+        format!(
+            "{}",
+            parse_and_eval("case (42, False) of (x, b) when b => x | _ => 0 end").unwrap()
+        )
+    );
+
+    assert!(parse_and_eval("case True of b when False => 1 end").is_err());
+
+    assert_eq!(
+        "(2, 3)",
+        format!(
+            "{}",
+            parse_and_eval("case { x = 1, y = 2, z = 3 } of { y, z = w, .. } => (y, w) end")
+                .unwrap()
+        )
+    );
+
+    assert_eq!(
+        "42",
+        format!(
+            "{}",
+            parse_and_eval("case { a = 1, b = 2 } of { b = 1, .. } => 0 | { a, b } => 42 end")
+                .unwrap()
+        )
+    );
+}
+
+#[test]
+fn eval_if() {
+    assert_eq!(
+        "42",
+        format!("{}", parse_and_eval("if True then 42 else 43").unwrap())
+    );
+
+    assert_eq!(
+        "43",
+        format!(
+            "{}",
+            parse_and_eval("let f = lambda b -> if b then 42 else 43 in f False end").unwrap()
+        )
+    );
+
+    // Only the chosen branch is evaluated
+    assert_eq!(
+        "42",
+        format!(
+            "{}",
+            parse_and_eval("if True then 42 else undefined").unwrap()
+        )
+    );
+}
+
+#[test]
+fn eval_record_access() {
+    assert_eq!(
+        "42",
+        format!("{}", parse_and_eval("{ x = 42, y = True }.x").unwrap())
+    );
+
+    assert_eq!(
+        "{x = 43, y = true}",
+        format!(
+            "{}",
+            parse_and_eval("let r = { x = 42, y = True } in { r with x = 43 } end").unwrap()
+        )
+    );
+}
+
+#[test]
+fn eval_letrec() {
+    assert_eq!(
+        "42",
+        format!(
+            "{}",
+            parse_and_eval("let rec f = lambda b -> if b then f False else 42 in f True end")
+                .unwrap()
+        )
+    );
+
+    assert_eq!(
+        "1",
+        format!(
+            "{}",
+            parse_and_eval(
+                "let rec ping = lambda b -> if b then pong b else 1 \
+                 and pong = lambda b -> ping False in ping True end"
+            )
+            .unwrap()
+        )
+    );
+}
+
+#[test]
+fn eval_constructors() {
+    fn eval_with_list(input: &str) -> Result<Object> {
+        let env = Environment::new()
+            .insert("Nil", constructor("Nil", 0))
+            .insert("Cons", constructor("Cons", 2));
+        let e = crate::parse::parse_exprs(
+            crate::parse::Parser::parse(crate::parse::Rule::expr, input)
+                .unwrap_or_else(|e| panic!("{}", e))
+                .next()
+                .unwrap()
+                .into_inner(),
+        )
+        .unwrap();
+        eval(&env, e)
+    }
+
+    assert_eq!(
+        "Cons 1 (Cons 2 Nil)",
+        format!("{}", eval_with_list("Cons 1 (Cons 2 Nil)").unwrap())
+    );
+
+    assert_eq!(
+        "2",
+        format!(
+            "{}",
+            eval_with_list(
+                "let rec last = lambda l -> case l of Cons x Nil => x | Cons _ xs => last xs end \
+                 in last (Cons 1 (Cons 2 Nil)) end"
+            )
+            .unwrap()
+        )
+    );
+
+    assert!(eval_with_list("Cons 1 Nil").unwrap() == eval_with_list("Cons 1 Nil").unwrap());
+
+    assert!(eval_with_list("Cons 1 Nil").unwrap() != eval_with_list("Cons 2 Nil").unwrap());
+}
+
+#[test]
+fn eval_list() {
+    assert_eq!(
+        "[1, 2, 3]",
+        format!("{}", parse_and_eval("1 :: [2, 3]").unwrap())
+    );
+
+    assert_eq!("[]", format!("{}", parse_and_eval("[]").unwrap()));
+
+    assert_eq!(
+        "3",
+        format!(
+            "{}",
+            parse_and_eval(
+                "let rec sum = lambda l -> case l of [] => 0 | [x] => x | x :: xs => sum xs end \
+                 in sum [1, 2, 3] end"
+            )
+            .unwrap()
+        )
+    );
+
+    assert_eq!(
+        "(1, [2, 3])",
+        format!(
+            "{}",
+            parse_and_eval("case [1, 2, 3] of [] => (0, []) | x :: xs => (x, xs) end").unwrap()
+        )
+    );
+
+    assert!(parse_and_eval("[1, 2]").unwrap() == parse_and_eval("1 :: 2 :: []").unwrap());
+
+    assert!(parse_and_eval("[1, 2]").unwrap() != parse_and_eval("[2, 1]").unwrap());
+}
+
+#[test]
+fn eval_newtypes() {
+    fn eval_with_newtype(input: &str) -> Result<Object> {
+        let env = Environment::new()
+            .insert("UserId", newtype_constructor())
+            .insert("Some", constructor("Some", 1));
+        let e = crate::parse::parse_exprs(
+            crate::parse::Parser::parse(crate::parse::Rule::expr, input)
+                .unwrap_or_else(|e| panic!("{}", e))
+                .next()
+                .unwrap()
+                .into_inner(),
+        )
+        .unwrap();
+        let newtypes = vec!["UserId".to_string()].into_iter().collect();
+        eval(&env, erase_newtypes(&newtypes, e))
+    }
+
+    // A newtype value is the value it wraps
+    assert_eq!("42", format!("{}", eval_with_newtype("UserId 42").unwrap()));
+
+    assert_eq!(
+        "(1, 2)",
+        format!(
+            "{}",
+            eval_with_newtype(
+                "case (UserId 1, Some (UserId 2)) of (UserId x, Some (UserId y)) => (x, y) end"
+            )
+            .unwrap()
+        )
+    );
+
+    assert_eq!(
+        "3",
+        format!(
+            "{}",
+            eval_with_newtype("let (UserId x, _) = (UserId 3, ()) in x end").unwrap()
+        )
+    );
+
+    // Patterns with the wrong number of arguments are left for the type
+    // checker to reject
+    let newtypes = vec!["UserId".to_string()].into_iter().collect();
+    for pats in [vec![], vec![Pattern::Wildcard, Pattern::Wildcard]] {
+        let pat = Pattern::Constructor("UserId".to_string(), pats);
+        assert_eq!(pat, erase_newtype_pat(&newtypes, pat.clone()));
+    }
 }
@@ -10,4 +10,6 @@ pub mod eval;
 pub mod name_source;
 pub mod object;
 pub mod parse;
+pub mod prelude;
 pub mod ty;
+pub mod wire;
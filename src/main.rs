@@ -1,13 +1,11 @@
 use anyhow::Result;
 use pdb::ast::Statement;
+use pdb::wire::{self, Reply};
 use serde_lexpr::from_str;
 use std::sync::mpsc::channel;
 use std::sync::mpsc::Sender;
-use std::thread;
 use structopt::StructOpt;
-use tokio::io::AsyncBufReadExt;
 use tokio::io::AsyncWrite;
-use tokio::io::AsyncWriteExt;
 use tokio::io::BufReader;
 use tokio::net::TcpListener;
 use tokio::prelude::AsyncBufRead;
@@ -32,7 +30,7 @@ async fn main(args: Args) -> Result<()> {
     let (tx, rx) = channel();
 
     // Spawn Db handler thread
-    let _ = thread::spawn(|| pdb::db::start(rx));
+    let _ = pdb::db::spawn(rx);
 
     let mut listener = TcpListener::bind(("127.0.0.1", args.config.port)).await?;
 
@@ -52,39 +50,168 @@ async fn start(
     mut stream: impl AsyncBufRead + AsyncWrite + Unpin,
 ) {
     loop {
-        let mut buffer = String::new();
-
-        match stream.read_line(&mut buffer).await {
-            Ok(_) => match from_str(&buffer) {
+        let reply: Reply = match wire::receive_line(&mut stream).await {
+            Ok(Some(line)) => match from_str(&line) {
                 Ok(stm) => {
                     println!("Got {:?}", stm);
                     let (tx2, rx2) = channel();
 
                     tx.send((stm, tx2)).expect("unimplemented");
 
-                    stream
-                        .write_all({
-                            let res = rx2.recv();
-                            res.expect("unimplemented").unwrap().to_string().as_bytes()
-                        })
-                        .await
-                        .unwrap();
-
-                    stream.flush().await.unwrap();
-                }
-                Err(e) => {
-                    stream
-                        .write_all(format!("No parse: {}", e).as_bytes())
-                        .await
-                        .unwrap();
-
-                    stream.flush();
+                    rx2.recv()
+                        .expect("unimplemented")
+                        .map_err(|e| e.to_string())
                 }
+                Err(e) => Err(format!("No parse: {}", e)),
             },
+            Ok(None) => break,
             Err(e) => {
                 eprintln!("failed to read from stream; err = {:?}", e);
                 break;
             }
+        };
+
+        if let Err(e) = wire::send(&mut stream, &reply).await {
+            eprintln!("failed to write to stream; err = {:?}", e);
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pdb::parse::parse;
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use tokio::io::AsyncRead;
+
+    /// A connection on which the client has sent `input` and then closed its
+    /// end. What the server writes ends up in `output`.
+    struct Connection<'a> {
+        input: &'a [u8],
+        output: Vec<u8>,
+    }
+
+    impl<'a> AsyncRead for Connection<'a> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.get_mut().input).poll_read(cx, buf)
+        }
+    }
+
+    impl<'a> AsyncBufRead for Connection<'a> {
+        fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+            Pin::new(&mut self.get_mut().input).poll_fill_buf(cx)
+        }
+
+        fn consume(self: Pin<&mut Self>, amt: usize) {
+            Pin::new(&mut self.get_mut().input).consume(amt)
+        }
+    }
+
+    impl<'a> AsyncWrite for Connection<'a> {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.get_mut().output).poll_write(cx, buf)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.get_mut().output).poll_flush(cx)
         }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.get_mut().output).poll_shutdown(cx)
+        }
+    }
+
+    /// Send the statements in `inputs` to a fresh server and return its replies
+    async fn run(inputs: &[&str]) -> Vec<Reply> {
+        let (tx, rx) = channel();
+        let _ = pdb::db::spawn(rx);
+
+        let mut input = Vec::new();
+        for stm in inputs {
+            wire::send(&mut input, &parse(stm).unwrap()).await.unwrap();
+        }
+
+        let mut conn = Connection {
+            input: &input,
+            output: Vec::new(),
+        };
+        start(tx, &mut conn).await;
+
+        let mut output = &conn.output[..];
+        let mut replies = Vec::new();
+        while let Some(reply) = wire::receive(&mut output).await.unwrap() {
+            replies.push(reply);
+        }
+
+        replies
+    }
+
+    #[tokio::test]
+    async fn multi_line_replies() {
+        let replies = run(&[
+            "builtins",
+            "create table user Int",
+            "select from users",
+            "select from user",
+        ])
+        .await;
+
+        assert_eq!(4, replies.len());
+
+        let builtins = replies[0].as_ref().unwrap();
+        assert!(builtins.lines().count() > 1);
+        assert!(builtins.contains("(+): Num a => a -> a -> a\n"));
+
+        // The statements after a multi-line reply get their own replies
+        assert_eq!(
+            vec![
+                Ok("Created\n".to_string()),
+                Err("No such table\n".to_string()),
+                Ok("[]\n".to_string())
+            ],
+            replies[1..]
+        );
+    }
+
+    #[tokio::test]
+    async fn destructuring_let_replies() {
+        assert_eq!(
+            vec![
+                Ok("a: Int\nb: Bool\n".to_string()),
+                Ok("a: Int\n".to_string())
+            ],
+            run(&["let (a, b) = (1, True)", "typeof a"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn let_rec_replies() {
+        let even_odd = "let rec even n = if n == 0 then True else odd (n - 1) \
+                        and odd n = if n == 0 then False else even (n - 1)";
+
+        let replies = run(&[even_odd, "let ev = even 10001", "let ev = even 1000"]).await;
+
+        assert_eq!(
+            vec![
+                Ok("even: Int -> Bool\nodd: Int -> Bool\n".to_string()),
+                Err(format!(
+                    "Evaluation is nested more than {} calls deep",
+                    pdb::eval::MAX_DEPTH
+                )),
+                Ok("ev: Bool\n".to_string())
+            ],
+            replies
+        );
     }
 }
@@ -1,16 +1,29 @@
-use crate::ast::Ident;
+use crate::ast::{fmt_string, fmt_timestamp, Ident};
 use anyhow::Result;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use num_bigint::BigInt;
+use rust_decimal::Decimal;
+use std::cmp::Ordering;
 use std::fmt;
 use std::rc::Rc;
 
 #[derive(Clone)]
 pub enum Object {
     Int(i64),
+    Float(f64),
+    BigInt(BigInt),
+    Decimal(Decimal),
+    Date(NaiveDate),
+    Time(NaiveTime),
+    Timestamp(DateTime<Utc>),
+    Bytes(Vec<u8>),
     Bool(bool),
     Tuple(Vec<Object>),
+    List(Vec<Object>),
     Unit,
     String(String),
     Record(Vec<(Ident, Object)>),
+    Variant(Ident, Vec<Object>),
     Closure(Rc<dyn Fn(Object) -> Result<Object>>),
 }
 
@@ -21,20 +34,66 @@ impl PartialEq for Object {
 
         match (self, other) {
             (Int(i1), Int(i2)) => i1 == i2,
+            (Float(x1), Float(x2)) => x1 == x2,
+            (BigInt(i1), BigInt(i2)) => i1 == i2,
+            (Decimal(d1), Decimal(d2)) => d1 == d2,
+            (Date(d1), Date(d2)) => d1 == d2,
+            (Time(t1), Time(t2)) => t1 == t2,
+            (Timestamp(ts1), Timestamp(ts2)) => ts1 == ts2,
+            (Bytes(b1), Bytes(b2)) => b1 == b2,
             (Bool(b1), Bool(b2)) => b1 == b2,
             (Tuple(objs1), Tuple(objs2)) => {
                 objs1.len() == objs2.len() && objs1.iter().eq(objs2.iter())
             }
+            (List(objs1), List(objs2)) => {
+                objs1.len() == objs2.len() && objs1.iter().eq(objs2.iter())
+            }
             (Unit, Unit) => true,
             (String(s1), String(s2)) => s1 == s2,
             (Record(recs1), Record(recs2)) => {
                 recs1.len() == recs2.len() && recs1.iter().eq(recs2.iter())
             }
+            (Variant(name1, objs1), Variant(name2, objs2)) => {
+                name1 == name2 && objs1.iter().eq(objs2.iter())
+            }
             _ => false,
         }
     }
 }
 
+/// Structural ordering. Variants are ordered by constructor name, and functions
+/// can't be compared at all. Floats are ordered as usual, except that NaN is
+/// ordered as equal to itself and greater than every other float, like NaN is
+/// sorted in SQL databases such as PostgreSQL.
+impl PartialOrd for Object {
+    fn partial_cmp(&self, other: &Object) -> Option<Ordering> {
+        use Object::*;
+
+        match (self, other) {
+            (Int(i1), Int(i2)) => i1.partial_cmp(i2),
+            (Float(x1), Float(x2)) => Some(match (x1.is_nan(), x2.is_nan()) {
+                (false, false) => x1.partial_cmp(x2).unwrap(),
+                (nan1, nan2) => nan1.cmp(&nan2),
+            }),
+            (BigInt(i1), BigInt(i2)) => i1.partial_cmp(i2),
+            (Decimal(d1), Decimal(d2)) => d1.partial_cmp(d2),
+            (Date(d1), Date(d2)) => d1.partial_cmp(d2),
+            (Time(t1), Time(t2)) => t1.partial_cmp(t2),
+            (Timestamp(ts1), Timestamp(ts2)) => ts1.partial_cmp(ts2),
+            (Bytes(b1), Bytes(b2)) => b1.partial_cmp(b2),
+            (Bool(b1), Bool(b2)) => b1.partial_cmp(b2),
+            (Tuple(objs1), Tuple(objs2)) | (List(objs1), List(objs2)) => objs1.partial_cmp(objs2),
+            (Unit, Unit) => Some(Ordering::Equal),
+            (String(s1), String(s2)) => s1.partial_cmp(s2),
+            (Record(recs1), Record(recs2)) => recs1.partial_cmp(recs2),
+            (Variant(name1, objs1), Variant(name2, objs2)) => {
+                (name1, objs1).partial_cmp(&(name2, objs2))
+            }
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Debug for Object {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Display::fmt(self, f)
@@ -45,6 +104,13 @@ impl fmt::Display for Object {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Object::Int(i) => write!(f, "{}", i),
+            Object::Float(x) => write!(f, "{:?}", x),
+            Object::BigInt(i) => write!(f, "{}n", i),
+            Object::Decimal(d) => write!(f, "{}d", d),
+            Object::Date(date) => write!(f, "@{}", date),
+            Object::Time(time) => write!(f, "@{}", time),
+            Object::Timestamp(ts) => write!(f, "@{}", fmt_timestamp(ts)),
+            Object::Bytes(bytes) => write!(f, "hex\"{}\"", hex::encode(bytes)),
             Object::Bool(b) => write!(f, "{}", b),
             Object::Tuple(objs) => {
                 let mut objs = objs.iter();
@@ -60,8 +126,22 @@ impl fmt::Display for Object {
 
                 write!(f, ")")
             }
+            Object::List(objs) => {
+                let mut objs = objs.iter();
+                write!(f, "[")?;
+
+                if let Some(obj) = objs.next() {
+                    write!(f, "{}", obj)?;
+                }
+
+                for obj in objs {
+                    write!(f, ", {}", obj)?;
+                }
+
+                write!(f, "]")
+            }
             Object::Unit => write!(f, "()"),
-            Object::String(b) => write!(f, "{}", b),
+            Object::String(s) => write!(f, "{}", fmt_string(s)),
             Object::Record(pairs) => {
                 let mut pairs = pairs.iter();
                 write!(f, "{{")?;
@@ -76,6 +156,16 @@ impl fmt::Display for Object {
 
                 write!(f, "}}")
             }
+            Object::Variant(name, objs) => {
+                write!(f, "{}", name)?;
+                for obj in objs {
+                    match obj {
+                        Object::Variant(_, args) if !args.is_empty() => write!(f, " ({})", obj)?,
+                        _ => write!(f, " {}", obj)?,
+                    }
+                }
+                Ok(())
+            }
             Object::Closure(_) => write!(f, "<lambda>"),
         }
     }
@@ -1,6 +1,10 @@
+#![allow(clippy::result_large_err)]
+
 use crate::ast::*;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, TimeZone, Utc};
 use pest::error::Error;
 use pest::iterators::{Pair, Pairs};
+use pest::prec_climber::{Assoc, Operator, PrecClimber};
 use pest::Parser as _;
 
 #[cfg(test)]
@@ -12,15 +16,21 @@ pub struct Parser;
 
 fn parse_tyrecord(mut pairs: Pairs<Rule>) -> Result<Ty, Error<Rule>> {
     let mut xs = Vec::new();
+    let mut row = None;
+
+    while let Some(pair) = pairs.next() {
+        if pair.as_rule() == Rule::tyrow {
+            row = Some(pair.into_inner().as_str().to_owned());
+            break;
+        }
 
-    while let Some(ident) = pairs.next() {
         let ty = parse_ty(pairs.next().unwrap().into_inner())?;
-        xs.push((ident.as_str().to_owned(), ty));
+        xs.push((pair.as_str().to_owned(), ty));
     }
 
     xs.sort_by(|(x, _), (y, _)| x.cmp(y));
 
-    Ok(Ty::Record(xs))
+    Ok(Ty::Record(xs, row))
 }
 
 pub fn parse_tyterm(pair: Pair<Rule>) -> Result<Ty, Error<Rule>> {
@@ -32,9 +42,19 @@ pub fn parse_tyterm(pair: Pair<Rule>) -> Result<Ty, Error<Rule>> {
                 .collect::<Result<Vec<_>, _>>()?,
         )),
         Rule::tyrecord => parse_tyrecord(pair.into_inner()),
+        Rule::tylist => Ok(Ty::List(Box::new(parse_ty(
+            pair.into_inner().next().unwrap().into_inner(),
+        )?))),
         Rule::identifier => Ok(Ty::Var(pair.as_str().to_string())),
         Rule::tyident => match pair.as_str() {
             "Int" => Ok(Ty::Int),
+            "Float" => Ok(Ty::Float),
+            "BigInt" => Ok(Ty::BigInt),
+            "Decimal" => Ok(Ty::Decimal),
+            "Date" => Ok(Ty::Date),
+            "Time" => Ok(Ty::Time),
+            "Timestamp" => Ok(Ty::Timestamp),
+            "Bytes" => Ok(Ty::Bytes),
             "Bool" => Ok(Ty::Bool),
             "String" => Ok(Ty::String),
             _ => Ok(Ty::Defined(pair.as_str().to_string(), vec![])),
@@ -85,34 +105,163 @@ fn parse_record(mut pairs: Pairs<Rule>) -> Result<Expr, Error<Rule>> {
     Ok(Expr::Record(xs))
 }
 
+fn parse_record_update(mut pairs: Pairs<Rule>) -> Result<Expr, Error<Rule>> {
+    let record = parse_exprs(pairs.next().unwrap().into_inner())?;
+
+    let mut xs = Vec::new();
+
+    while let Some(ident) = pairs.next() {
+        let expr = parse_exprs(pairs.next().unwrap().into_inner())?;
+        xs.push((ident.as_str().to_owned(), expr));
+    }
+
+    xs.sort_by(|(x, _), (y, _)| x.cmp(y));
+
+    Ok(Expr::Update(Box::new(record), xs))
+}
+
 fn parse_let(mut pairs: Pairs<Rule>) -> Result<Expr, Error<Rule>> {
     let mut binds = Vec::new();
 
     loop {
         if let Some(pair) = pairs.next() {
             match pair.as_rule() {
-                Rule::identifier => {
-                    let expr = parse_exprs(pairs.next().unwrap().into_inner())?;
-                    binds.push((pair.as_str().to_string(), expr));
+                Rule::fun_lhs | Rule::pattern => {
+                    binds.push(parse_binding(pair, &mut pairs)?);
                 }
                 Rule::expr => {
                     let expr = parse_exprs(pair.into_inner())?;
                     return Ok(Expr::Let(binds, Box::new(expr)));
                 }
-                other => {
-                    unimplemented!("Invalid pair inside let-rule: {:?}", other);
+                r => {
+                    return Err(Error::new_from_span(
+                        pest::error::ErrorVariant::CustomError {
+                            message: format!(
+                                "Unexpected rule {:?}, expected fun_lhs, pattern or expr",
+                                r
+                            ),
+                        },
+                        pair.as_span(),
+                    ))
                 }
             }
         } else {
-            unimplemented!("Invalid let binding {:?}", pairs)
+            unreachable!("The grammar ends every let expression with an expr")
         }
     }
 }
 
-fn parse_lambda(mut pairs: Pairs<Rule>) -> Result<Expr, Error<Rule>> {
-    let ident = pairs.next().unwrap().as_str().to_string();
-    let expr = parse_exprs(pairs.next().unwrap().into_inner())?;
-    Ok(Expr::Lambda(ident, Box::new(expr)))
+fn parse_letrec(mut pairs: Pairs<Rule>) -> Result<Expr, Error<Rule>> {
+    let mut binds = Vec::new();
+
+    while let Some(pair) = pairs.next() {
+        match pair.as_rule() {
+            Rule::identifier => {
+                binds.push((pair.as_str().to_string(), parse_fun(&mut pairs)?));
+            }
+            Rule::expr => {
+                let expr = parse_exprs(pair.into_inner())?;
+                return Ok(Expr::LetRec(binds, Box::new(expr)));
+            }
+            r => {
+                return Err(Error::new_from_span(
+                    pest::error::ErrorVariant::CustomError {
+                        message: format!("Unexpected rule {:?}, expected identifier or expr", r),
+                    },
+                    pair.as_span(),
+                ))
+            }
+        }
+    }
+
+    unreachable!("The grammar ends every let rec expression with an expr")
+}
+
+/// Parse a non-recursive let binding, which either binds a pattern or declares
+/// a function as in `let f x y = ...`.
+fn parse_binding(lhs: Pair<Rule>, pairs: &mut Pairs<Rule>) -> Result<(Pattern, Expr), Error<Rule>> {
+    match lhs.as_rule() {
+        Rule::fun_lhs => {
+            let mut inner = lhs.into_inner();
+            let ident = inner.next().unwrap().as_str().to_string();
+            // An annotation on a function declaration is the type of its result
+            let body = parse_annotated(pairs)?;
+            Ok((Pattern::Ident(ident), parse_params(inner, body)?))
+        }
+        _ => Ok((parse_pat(lhs.into_inner())?, parse_annotated(pairs)?)),
+    }
+}
+
+/// Parse the parameters and body of a recursive binding, `f x y = ...`
+fn parse_fun(pairs: &mut Pairs<Rule>) -> Result<Expr, Error<Rule>> {
+    let mut params = Vec::new();
+    while let Some(Rule::param) = pairs.peek().map(|x| x.as_rule()) {
+        params.push(pairs.next().unwrap());
+    }
+
+    let body = parse_exprs(pairs.next().unwrap().into_inner())?;
+    parse_params(params.into_iter(), body)
+}
+
+/// Turn a function with several parameters into a chain of lambdas taking one
+/// parameter each. A pattern parameter is bound to `LAMBDA_ARG` and destructured
+/// by a let binding in the body of its lambda.
+fn parse_params<'a>(
+    params: impl DoubleEndedIterator<Item = Pair<'a, Rule>>,
+    mut body: Expr,
+) -> Result<Expr, Error<Rule>> {
+    for param in params.rev() {
+        let param = param.into_inner().next().unwrap();
+        body = match param.as_rule() {
+            Rule::identifier => Expr::Lambda(param.as_str().to_string(), None, Box::new(body)),
+            Rule::annot_param => {
+                let mut inner = param.into_inner();
+                let ident = inner.next().unwrap().as_str().to_string();
+                let ty = parse_ty(inner.next().unwrap().into_inner())?;
+                Expr::Lambda(ident, Some(ty), Box::new(body))
+            }
+            _ => match parse_simple_pat(param)? {
+                Pattern::Ident(ident) => Expr::Lambda(ident, None, Box::new(body)),
+                pat => Expr::Lambda(
+                    LAMBDA_ARG.to_string(),
+                    None,
+                    Box::new(Expr::Let(
+                        vec![(pat, Expr::Ident(LAMBDA_ARG.to_string()))],
+                        Box::new(body),
+                    )),
+                ),
+            },
+        };
+    }
+
+    Ok(body)
+}
+
+/// Parse the right-hand side of a let binding, which may be preceded by a type
+/// annotation as in `let f : Int -> Int = ...`.
+fn parse_annotated(pairs: &mut Pairs<Rule>) -> Result<Expr, Error<Rule>> {
+    let pair = pairs.next().unwrap();
+    match pair.as_rule() {
+        Rule::ty => {
+            let ty = parse_ty(pair.into_inner())?;
+            let expr = parse_exprs(pairs.next().unwrap().into_inner())?;
+            Ok(Expr::Annot(Box::new(expr), ty))
+        }
+        _ => parse_exprs(pair.into_inner()),
+    }
+}
+
+fn parse_lambda(pairs: Pairs<Rule>) -> Result<Expr, Error<Rule>> {
+    let mut params: Vec<_> = pairs.collect();
+    let expr = parse_exprs(params.pop().unwrap().into_inner())?;
+    parse_params(params.into_iter(), expr)
+}
+
+fn parse_ifelse(mut pairs: Pairs<Rule>) -> Result<Expr, Error<Rule>> {
+    let cond = parse_exprs(pairs.next().unwrap().into_inner())?;
+    let then = parse_exprs(pairs.next().unwrap().into_inner())?;
+    let els = parse_exprs(pairs.next().unwrap().into_inner())?;
+    Ok(Expr::If(Box::new(cond), Box::new(then), Box::new(els)))
 }
 
 fn parse_case(mut pairs: Pairs<Rule>) -> Result<Expr, Error<Rule>> {
@@ -121,41 +270,116 @@ fn parse_case(mut pairs: Pairs<Rule>) -> Result<Expr, Error<Rule>> {
     let mut matches = Vec::new();
 
     while let Some(pat) = pairs.next() {
-        let pat_expr = pairs.next().unwrap().into_inner();
-        matches.push((parse_pat(pat.into_inner())?, parse_exprs(pat_expr)?));
+        let pat = parse_pat(pat.into_inner())?;
+
+        let mut next = pairs.next().unwrap();
+        let guard = if next.as_rule() == Rule::guard {
+            let guard = parse_exprs(next.into_inner().next().unwrap().into_inner())?;
+            next = pairs.next().unwrap();
+            Some(guard)
+        } else {
+            None
+        };
+
+        matches.push((pat, guard, parse_exprs(next.into_inner())?));
     }
 
     Ok(Expr::Case(Box::new(expr), matches))
 }
 
-fn parse_pat(mut pairs: Pairs<Rule>) -> Result<Pattern, Error<Rule>> {
-    let pat = pairs.next().unwrap();
+fn parse_pat(pairs: Pairs<Rule>) -> Result<Pattern, Error<Rule>> {
+    let mut alternatives = Vec::new();
+    let mut name = None;
+
+    for pair in pairs {
+        match pair.as_rule() {
+            Rule::cons_pat => alternatives.push(parse_cons_pat(pair.into_inner())?),
+            // The name after `as`
+            _ => name = Some(pair.as_str().to_string()),
+        }
+    }
+
+    let pat = if alternatives.len() == 1 {
+        alternatives.pop().unwrap()
+    } else {
+        Pattern::Or(alternatives)
+    };
+
+    match name {
+        Some(name) => Ok(Pattern::As(Box::new(pat), name)),
+        None => Ok(pat),
+    }
+}
+
+fn parse_cons_pat(mut pairs: Pairs<Rule>) -> Result<Pattern, Error<Rule>> {
+    let head = parse_simple_pat(pairs.next().unwrap())?;
+
+    match pairs.next() {
+        Some(tail) => Ok(Pattern::Cons(
+            Box::new(head),
+            Box::new(parse_cons_pat(tail.into_inner())?),
+        )),
+        None => Ok(head),
+    }
+}
+
+fn parse_simple_pat(pat: Pair<Rule>) -> Result<Pattern, Error<Rule>> {
     match pat.as_rule() {
         Rule::atom => Ok(Pattern::Atom(parse_atom(pat.into_inner().next().unwrap())?)),
+        Rule::unit => Ok(Pattern::Atom(Atom::Unit)),
         Rule::tuple_pat => {
             let mut pats = Vec::new();
 
-            for pair in pat.into_inner().into_iter() {
+            for pair in pat.into_inner() {
                 pats.push(parse_pat(pair.into_inner())?);
             }
 
             Ok(Pattern::Tuple(pats))
         }
+        Rule::list_pat => Ok(Pattern::List(
+            pat.into_inner()
+                .map(|x| parse_pat(x.into_inner()))
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
         Rule::record_pat => {
             let mut xs = Vec::new();
-            let mut pairs = pat.into_inner();
+            let mut open = false;
 
-            while let Some(ident) = pairs.next() {
-                let ty = parse_pat(pairs.next().unwrap().into_inner())?;
-                xs.push((ident.as_str().to_owned(), ty));
+            for pair in pat.into_inner() {
+                if pair.as_rule() == Rule::rest {
+                    open = true;
+                    continue;
+                }
+
+                let mut pairs = pair.into_inner();
+                let ident = pairs.next().unwrap().as_str().to_owned();
+                let pat = match pairs.next() {
+                    Some(pat) => parse_pat(pat.into_inner())?,
+                    None => Pattern::Ident(ident.clone()),
+                };
+                xs.push((ident, pat));
             }
 
             xs.sort_by(|(x, _), (y, _)| x.cmp(y));
 
-            Ok(Pattern::Record(xs))
+            Ok(Pattern::Record(xs, open))
+        }
+        Rule::con_pat => {
+            let mut pairs = pat.into_inner();
+            let name = pairs.next().unwrap().as_str().to_string();
+
+            let mut pats = Vec::new();
+
+            for pair in pairs {
+                pats.push(parse_simple_pat(pair.into_inner().next().unwrap())?);
+            }
+
+            Ok(Pattern::Constructor(name, pats))
         }
         Rule::wildcard => Ok(Pattern::Wildcard),
         Rule::identifier => Ok(Pattern::Ident(pat.as_str().to_string())),
+        Rule::tyident => Ok(Pattern::Constructor(pat.as_str().to_string(), vec![])),
+        Rule::pattern => parse_pat(pat.into_inner()),
         r => Err(Error::new_from_span(
             pest::error::ErrorVariant::CustomError {
                 message: format!("Unexpected rule {:?}, expected pattern", r),
@@ -165,14 +389,172 @@ fn parse_pat(mut pairs: Pairs<Rule>) -> Result<Pattern, Error<Rule>> {
     }
 }
 
+fn parse_date(pair: Pair<Rule>) -> Result<NaiveDate, Error<Rule>> {
+    NaiveDate::parse_from_str(pair.as_str(), "%Y-%m-%d").map_err(|e| {
+        Error::new_from_span(
+            pest::error::ErrorVariant::CustomError {
+                message: format!("Invalid date: {}", e),
+            },
+            pair.as_span(),
+        )
+    })
+}
+
+fn parse_time(pair: Pair<Rule>) -> Result<NaiveTime, Error<Rule>> {
+    NaiveTime::parse_from_str(pair.as_str(), "%H:%M:%S%.f")
+        .or_else(|_| NaiveTime::parse_from_str(pair.as_str(), "%H:%M"))
+        .map_err(|e| {
+            Error::new_from_span(
+                pest::error::ErrorVariant::CustomError {
+                    message: format!("Invalid time: {}", e),
+                },
+                pair.as_span(),
+            )
+        })
+}
+
+fn parse_timestamp(mut pairs: Pairs<Rule>) -> Result<DateTime<Utc>, Error<Rule>> {
+    let date = parse_date(pairs.next().unwrap())?;
+    let time = parse_time(pairs.next().unwrap())?;
+    let offset = pairs.next().unwrap();
+
+    let seconds = match offset.as_str() {
+        "Z" => Some(0),
+        s => match (s[1..3].parse::<i32>(), s[4..6].parse::<i32>()) {
+            (Ok(h), Ok(m)) if h < 24 && m < 60 => {
+                let seconds = h * 3600 + m * 60;
+                Some(if s.starts_with('-') {
+                    -seconds
+                } else {
+                    seconds
+                })
+            }
+            _ => None,
+        },
+    };
+
+    seconds
+        .and_then(FixedOffset::east_opt)
+        .and_then(|offset| offset.from_local_datetime(&date.and_time(time)).single())
+        .map(|ts| ts.with_timezone(&Utc))
+        .ok_or_else(|| {
+            Error::new_from_span(
+                pest::error::ErrorVariant::CustomError {
+                    message: "Invalid UTC offset".to_string(),
+                },
+                offset.as_span(),
+            )
+        })
+}
+
+/// Decode the escape sequences in the contents of a string literal. A `\u`
+/// escape may be a UTF-16 surrogate pair, like in JSON.
+fn parse_string(pair: Pair<Rule>) -> Result<String, Error<Rule>> {
+    let invalid = || {
+        Error::new_from_span(
+            pest::error::ErrorVariant::CustomError {
+                message: "Invalid unicode escape".to_string(),
+            },
+            pair.as_span(),
+        )
+    };
+
+    let mut res = String::new();
+    let mut rest = pair.as_str();
+
+    while let Some(i) = rest.find('\\') {
+        res.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+
+        let c = match rest.as_bytes()[0] {
+            b'b' => '\u{8}',
+            b'f' => '\u{c}',
+            b'n' => '\n',
+            b'r' => '\r',
+            b't' => '\t',
+            b'u' => {
+                let code = u32::from_str_radix(&rest[1..5], 16).unwrap();
+                if (0xD800..0xDC00).contains(&code) && rest[5..].starts_with("\\u") {
+                    let low = u32::from_str_radix(&rest[7..11], 16).unwrap();
+                    rest = &rest[6..];
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err(invalid());
+                    }
+                    std::char::from_u32(0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00))
+                } else {
+                    std::char::from_u32(code)
+                }
+                .ok_or_else(invalid)?
+            }
+            // `"`, `\\` and `/` stand for themselves
+            c => c as char,
+        };
+
+        res.push(c);
+        rest = &rest[if rest.starts_with('u') { 5 } else { 1 }..];
+    }
+
+    res.push_str(rest);
+    Ok(res)
+}
+
 pub fn parse_atom(atom: Pair<Rule>) -> Result<Atom, Error<Rule>> {
     match atom.as_rule() {
-        Rule::int => Ok(Atom::Int(atom.as_str().parse().unwrap())),
+        Rule::int => atom.as_str().parse().map(Atom::Int).map_err(|_| {
+            Error::new_from_span(
+                pest::error::ErrorVariant::CustomError {
+                    message: "Int literal is out of range, use the n suffix for a BigInt"
+                        .to_string(),
+                },
+                atom.as_span(),
+            )
+        }),
+        Rule::date => Ok(Atom::Date(parse_date(atom.into_inner().next().unwrap())?)),
+        Rule::time => Ok(Atom::Time(parse_time(atom.into_inner().next().unwrap())?)),
+        Rule::timestamp => Ok(Atom::Timestamp(parse_timestamp(atom.into_inner())?)),
+        Rule::bytes => {
+            let digits = atom.into_inner().next().unwrap();
+            let bytes = match digits.as_rule() {
+                Rule::hex_digits => hex::decode(digits.as_str()).map_err(|e| e.to_string()),
+                _ => base64::decode(digits.as_str()).map_err(|e| e.to_string()),
+            };
+            bytes.map(Atom::Bytes).map_err(|e| {
+                Error::new_from_span(
+                    pest::error::ErrorVariant::CustomError {
+                        message: format!("Invalid Bytes literal: {}", e),
+                    },
+                    digits.as_span(),
+                )
+            })
+        }
+        Rule::bigint => Ok(Atom::BigInt(
+            atom.into_inner().next().unwrap().as_str().parse().unwrap(),
+        )),
+        Rule::decimal => {
+            let digits = atom.into_inner().next().unwrap();
+            digits.as_str().parse().map(Atom::Decimal).map_err(|e| {
+                Error::new_from_span(
+                    pest::error::ErrorVariant::CustomError {
+                        message: format!("Invalid Decimal literal: {}", e),
+                    },
+                    digits.as_span(),
+                )
+            })
+        }
+        Rule::float => match atom.as_str().parse::<f64>() {
+            Ok(x) if x.is_finite() => Ok(Atom::Float(x)),
+            _ => Err(Error::new_from_span(
+                pest::error::ErrorVariant::CustomError {
+                    message: "Float literal is out of range".to_string(),
+                },
+                atom.as_span(),
+            )),
+        },
         Rule::bool => Ok(Atom::Bool(matches!(atom.as_str(), "True"))),
         Rule::unit => Ok(Atom::Unit),
-        Rule::string => Ok(Atom::String(
-            atom.into_inner().next().unwrap().as_str().to_string(),
-        )),
+        Rule::string => Ok(Atom::String(parse_string(
+            atom.into_inner().next().unwrap(),
+        )?)),
         r => Err(Error::new_from_span(
             pest::error::ErrorVariant::CustomError {
                 message: format!("Unexpected rule {:?}, expected atom", r),
@@ -192,10 +574,27 @@ pub fn parse_term(term: Pair<Rule>) -> Result<Expr, Error<Rule>> {
                 .map(|x| parse_exprs(x.into_inner()))
                 .collect::<Result<Vec<_>, _>>()?,
         )),
+        Rule::list => Ok(Expr::List(
+            term.into_inner()
+                .map(|x| parse_exprs(x.into_inner()))
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
         Rule::record => parse_record(term.into_inner()),
+        Rule::record_update => parse_record_update(term.into_inner()),
         Rule::letbind => parse_let(term.into_inner()),
+        Rule::letrec => parse_letrec(term.into_inner()),
         Rule::lambda => parse_lambda(term.into_inner()),
         Rule::case => parse_case(term.into_inner()),
+        Rule::ifelse => parse_ifelse(term.into_inner()),
+        Rule::annot => {
+            let mut pairs = term.into_inner();
+            let expr = parse_exprs(pairs.next().unwrap().into_inner())?;
+            let ty = parse_ty(pairs.next().unwrap().into_inner())?;
+            Ok(Expr::Annot(Box::new(expr), ty))
+        }
+        Rule::opsection => Ok(Expr::Ident(
+            term.into_inner().next().unwrap().as_str().to_string(),
+        )),
         Rule::expr => parse_exprs(term.into_inner()),
         r => Err(Error::new_from_span(
             pest::error::ErrorVariant::CustomError {
@@ -206,19 +605,73 @@ pub fn parse_term(term: Pair<Rule>) -> Result<Expr, Error<Rule>> {
     }
 }
 
-pub fn parse_exprs(mut exprs: Pairs<Rule>) -> Result<Expr, Error<Rule>> {
-    let mut res = parse_term(exprs.next().unwrap().into_inner().next().unwrap())?;
+/// Parse a term followed by any number of `.field` projections
+fn parse_field_access(mut pairs: Pairs<Rule>) -> Result<Expr, Error<Rule>> {
+    let mut res = parse_term(pairs.next().unwrap())?;
+
+    for field in pairs {
+        res = Expr::Field(Box::new(res), field.as_str().to_string());
+    }
 
-    for term in exprs {
+    Ok(res)
+}
+
+fn parse_application(mut terms: Pairs<Rule>) -> Result<Expr, Error<Rule>> {
+    let mut res = parse_field_access(terms.next().unwrap().into_inner())?;
+
+    for term in terms {
         res = Expr::Apply(
             Box::new(res),
-            Box::new(parse_term(term.into_inner().next().unwrap())?),
+            Box::new(parse_field_access(term.into_inner())?),
         );
     }
 
     Ok(res)
 }
 
+/// Operator precedence, from loosest to tightest binding
+fn climber() -> PrecClimber<Rule> {
+    use Assoc::*;
+
+    PrecClimber::new(vec![
+        Operator::new(Rule::eq, Left)
+            | Operator::new(Rule::neq, Left)
+            | Operator::new(Rule::lt, Left)
+            | Operator::new(Rule::le, Left)
+            | Operator::new(Rule::gt, Left)
+            | Operator::new(Rule::ge, Left),
+        Operator::new(Rule::cons, Right),
+        Operator::new(Rule::add, Left)
+            | Operator::new(Rule::sub, Left)
+            | Operator::new(Rule::fadd, Left)
+            | Operator::new(Rule::fsub, Left),
+        Operator::new(Rule::mul, Left)
+            | Operator::new(Rule::div, Left)
+            | Operator::new(Rule::rem, Left)
+            | Operator::new(Rule::fmul, Left)
+            | Operator::new(Rule::fdiv, Left),
+    ])
+}
+
+/// Parse applications separated by infix operators. Operators other than `::`
+/// are desugared to applications of the builtin of the same name.
+pub fn parse_exprs(exprs: Pairs<Rule>) -> Result<Expr, Error<Rule>> {
+    climber().climb(
+        exprs,
+        |pair| parse_application(pair.into_inner()),
+        |lhs, op, rhs| match op.as_rule() {
+            Rule::cons => Ok(Expr::Cons(Box::new(lhs?), Box::new(rhs?))),
+            _ => Ok(Expr::Apply(
+                Box::new(Expr::Apply(
+                    Box::new(Expr::Ident(op.as_str().to_string())),
+                    Box::new(lhs?),
+                )),
+                Box::new(rhs?),
+            )),
+        },
+    )
+}
+
 pub fn parse_select(mut pairs: Pairs<Rule>) -> Result<Statement, Error<Rule>> {
     let ident = pairs.next().unwrap().as_str();
 
@@ -240,10 +693,20 @@ pub fn parse_create(mut pairs: Pairs<Rule>) -> Result<Statement, Error<Rule>> {
 }
 
 pub fn parse_letdecl(mut pairs: Pairs<Rule>) -> Result<Statement, Error<Rule>> {
-    let ident = pairs.next().unwrap().as_str();
-    let expr = parse_exprs(pairs.next().unwrap().into_inner())?;
+    let lhs = pairs.next().unwrap();
+    let (pat, expr) = parse_binding(lhs, &mut pairs)?;
 
-    Ok(Statement::Let(ident.to_string(), expr))
+    Ok(Statement::Let(pat, expr))
+}
+
+pub fn parse_letrecdecl(mut pairs: Pairs<Rule>) -> Result<Statement, Error<Rule>> {
+    let mut binds = Vec::new();
+
+    while let Some(ident) = pairs.next() {
+        binds.push((ident.as_str().to_string(), parse_fun(&mut pairs)?));
+    }
+
+    Ok(Statement::LetRec(binds))
 }
 
 pub fn parse_longtyident(mut pairs: Pairs<Rule>) -> Result<Ty, Error<Rule>> {
@@ -251,11 +714,15 @@ pub fn parse_longtyident(mut pairs: Pairs<Rule>) -> Result<Ty, Error<Rule>> {
 
     let mut args = Vec::new();
 
-    while let Some(pair) = pairs.next() {
+    for pair in pairs {
         args.push(parse_tyterm(pair.into_inner().next().unwrap())?);
     }
 
-    Ok(Ty::Defined(ident, args))
+    if ident == "List" && args.len() == 1 {
+        Ok(Ty::List(Box::new(args.pop().unwrap())))
+    } else {
+        Ok(Ty::Defined(ident, args))
+    }
 }
 
 pub fn parse_tycon(mut pairs: Pairs<Rule>) -> Result<(Ident, Vec<Ty>), Error<Rule>> {
@@ -263,14 +730,14 @@ pub fn parse_tycon(mut pairs: Pairs<Rule>) -> Result<(Ident, Vec<Ty>), Error<Rul
 
     let mut args = Vec::new();
 
-    while let Some(pair) = pairs.next() {
+    for pair in pairs {
         args.push(parse_tyterm(pair.into_inner().next().unwrap())?);
     }
 
     Ok((ident, args))
 }
 
-pub fn parse_datatype(mut pairs: Pairs<Rule>) -> Result<Statement, Error<Rule>> {
+pub fn parse_tydecl(mut pairs: Pairs<Rule>) -> Result<UnionDecl, Error<Rule>> {
     let ident = pairs.next().unwrap().as_str();
 
     let mut args = Vec::new();
@@ -285,7 +752,54 @@ pub fn parse_datatype(mut pairs: Pairs<Rule>) -> Result<Statement, Error<Rule>>
         variants.push(parse_tycon(pair.into_inner())?);
     }
 
-    Ok(Statement::Union(ident.to_string(), args, variants))
+    Ok((ident.to_string(), args, variants))
+}
+
+pub fn parse_datatype(pairs: Pairs<Rule>) -> Result<Statement, Error<Rule>> {
+    let decls = pairs
+        .map(|pair| parse_tydecl(pair.into_inner()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Statement::Union(decls))
+}
+
+pub fn parse_tyalias(mut pairs: Pairs<Rule>) -> Result<Statement, Error<Rule>> {
+    let ident = pairs.next().unwrap().as_str().to_string();
+
+    let mut args = Vec::new();
+
+    while let Some(Rule::identifier) = pairs.peek().map(|x| x.as_rule()) {
+        args.push(pairs.next().unwrap().as_str().to_string());
+    }
+
+    let ty = parse_ty(pairs.next().unwrap().into_inner())?;
+
+    Ok(Statement::Alias(ident, args, ty))
+}
+
+pub fn parse_newtype(mut pairs: Pairs<Rule>) -> Result<Statement, Error<Rule>> {
+    let ident = pairs.next().unwrap().as_str().to_string();
+
+    let mut args = Vec::new();
+
+    while let Some(Rule::identifier) = pairs.peek().map(|x| x.as_rule()) {
+        args.push(pairs.next().unwrap().as_str().to_string());
+    }
+
+    let constructor = pairs.next().unwrap().as_str().to_string();
+    let ty = parse_tyterm(pairs.next().unwrap().into_inner().next().unwrap())?;
+
+    Ok(Statement::Newtype(ident, args, constructor, ty))
+}
+
+pub fn parse_typequery(mut pairs: Pairs<Rule>) -> Result<Statement, Error<Rule>> {
+    let pair = pairs.next().unwrap();
+    match pair.as_rule() {
+        Rule::tablekw => Ok(Statement::TypeOfTable(
+            pairs.next().unwrap().as_str().to_string(),
+        )),
+        _ => Ok(Statement::TypeOf(parse_exprs(pair.into_inner())?)),
+    }
 }
 
 pub fn parse_statement(pair: Pair<Rule>) -> Result<Statement, Error<Rule>> {
@@ -294,7 +808,12 @@ pub fn parse_statement(pair: Pair<Rule>) -> Result<Statement, Error<Rule>> {
         Rule::select => Ok(parse_select(pair.into_inner())?),
         Rule::insert => Ok(parse_insert(pair.into_inner())?),
         Rule::letdecl => Ok(parse_letdecl(pair.into_inner())?),
+        Rule::letrecdecl => Ok(parse_letrecdecl(pair.into_inner())?),
         Rule::datatype => Ok(parse_datatype(pair.into_inner())?),
+        Rule::tyalias => Ok(parse_tyalias(pair.into_inner())?),
+        Rule::newtype => Ok(parse_newtype(pair.into_inner())?),
+        Rule::typequery => Ok(parse_typequery(pair.into_inner())?),
+        Rule::builtins => Ok(Statement::Builtins),
         _ => Err(Error::new_from_span(
             pest::error::ErrorVariant::CustomError {
                 message: format!("Unexpected rule {:?}, expected statement", pair),
@@ -305,10 +824,7 @@ pub fn parse_statement(pair: Pair<Rule>) -> Result<Statement, Error<Rule>> {
 }
 
 pub fn parse(input: &str) -> Result<Statement, Error<Rule>> {
-    let statement = Parser::parse(Rule::statement, input)
-        .unwrap()
-        .next()
-        .unwrap();
+    let statement = Parser::parse(Rule::statement, input)?.next().unwrap();
 
     parse_statement(statement)
 }
@@ -1,4 +1,5 @@
 use pretty_assertions::assert_eq;
+use proptest::prelude::*;
 
 use super::Ty;
 use super::*;
@@ -17,28 +18,45 @@ fn parse_ty_helper(input: &str) -> Result<Ty, Error<Rule>> {
 fn parse_ty() -> Result<(), Error<Rule>> {
     use Ty::*;
 
-    assert_eq!(Int, parse_ty_helper(&"((Int))")?);
-    assert_eq!(Bool, parse_ty_helper(&"Bool")?);
-    assert_eq!(Tuple(vec!(Int, Bool)), parse_ty_helper(&"((Int, Bool))")?);
-    assert_eq!(Unit, parse_ty_helper(&"()")?);
-    assert_eq!(String, parse_ty_helper(&"String")?);
-    assert_eq!(Defined("Foo".to_string(), vec!()), parse_ty_helper(&"Foo")?);
-    assert_eq!(Int, parse_ty_helper(&"((Int))")?);
+    assert_eq!(Int, parse_ty_helper("((Int))")?);
+    assert_eq!(Bool, parse_ty_helper("Bool")?);
+    assert_eq!(Tuple(vec!(Int, Bool)), parse_ty_helper("((Int, Bool))")?);
+    assert_eq!(Unit, parse_ty_helper("()")?);
+    assert_eq!(String, parse_ty_helper("String")?);
+    assert_eq!(Float, parse_ty_helper("Float")?);
+    assert_eq!(BigInt, parse_ty_helper("BigInt")?);
+    assert_eq!(Decimal, parse_ty_helper("Decimal")?);
+    assert_eq!(Date, parse_ty_helper("Date")?);
+    assert_eq!(Time, parse_ty_helper("Time")?);
+    assert_eq!(Timestamp, parse_ty_helper("Timestamp")?);
+    assert_eq!(Bytes, parse_ty_helper("Bytes")?);
+    assert_eq!(Defined("Foo".to_string(), vec!()), parse_ty_helper("Foo")?);
+    assert_eq!(Int, parse_ty_helper("((Int))")?);
     assert_eq!(
         Fun(Box::new(Int), Box::new(Int)),
-        parse_ty_helper(&"(Int -> Int)")?
+        parse_ty_helper("(Int -> Int)")?
     );
     assert_eq!(
         Fun(Box::new(Fun(Box::new(Int), Box::new(Int))), Box::new(Int)),
-        parse_ty_helper(&"(Int -> Int) -> Int")?
+        parse_ty_helper("(Int -> Int) -> Int")?
     );
     assert_eq!(
         Fun(Box::new(Int), Box::new(Fun(Box::new(Int), Box::new(Int)))),
-        parse_ty_helper(&"Int -> Int -> Int")?
+        parse_ty_helper("Int -> Int -> Int")?
     );
     assert_eq!(
         Fun(Box::new(Int), Box::new(Fun(Box::new(Int), Box::new(Int)))),
-        parse_ty_helper(&"Int -> (Int -> Int)")?
+        parse_ty_helper("Int -> (Int -> Int)")?
+    );
+    assert_eq!(List(Box::new(Int)), parse_ty_helper("[Int]")?);
+    assert_eq!(List(Box::new(Int)), parse_ty_helper("List Int")?);
+    assert_eq!(
+        List(Box::new(List(Box::new(Var("a".to_string()))))),
+        parse_ty_helper("[List a]")?
+    );
+    assert_eq!(
+        Fun(Box::new(List(Box::new(Int))), Box::new(Int)),
+        parse_ty_helper("[Int] -> Int")?
     );
 
     Ok(())
@@ -60,14 +78,73 @@ fn parse_exprs() {
     use crate::ast::Atom::*;
     use Expr::*;
 
-    assert_eq!(Atom(Int(4)), parse_exprs_helper(&"4"));
-    assert_eq!(Ident("x".to_string()), parse_exprs_helper(&"x"));
+    assert_eq!(Atom(Int(4)), parse_exprs_helper("4"));
+    assert_eq!(Atom(Float(4.25)), parse_exprs_helper("4.25"));
+    assert_eq!(Atom(Float(-0.5)), parse_exprs_helper("-0.5"));
+    assert_eq!(Atom(Float(1.5e-7)), parse_exprs_helper("1.5e-7"));
+    assert_eq!(Atom(Float(2e10)), parse_exprs_helper("2E10"));
+    assert_eq!(
+        Atom(BigInt("-123456789012345678901234567890".parse().unwrap())),
+        parse_exprs_helper("-123456789012345678901234567890n")
+    );
+    assert_eq!(
+        Atom(Decimal("19.90".parse().unwrap())),
+        parse_exprs_helper("19.90d")
+    );
+    assert_eq!(Atom(Decimal(7.into())), parse_exprs_helper("7d"));
+    assert_eq!(
+        Atom(String("a\"b\\c/\n\r\t\u{8}\u{c}".to_string())),
+        parse_exprs_helper(r#""a\"b\\c\/\n\r\t\b\f""#)
+    );
+    assert_eq!(
+        Atom(String("é😀".to_string())),
+        parse_exprs_helper(r#""\u00E9\ud83d\ude00""#)
+    );
+    assert_eq!(
+        Atom(Date(chrono::NaiveDate::from_ymd_opt(2026, 10, 17).unwrap())),
+        parse_exprs_helper("@2026-10-17")
+    );
+    assert_eq!(
+        Atom(Time(chrono::NaiveTime::from_hms_opt(12, 30, 0).unwrap())),
+        parse_exprs_helper("@12:30")
+    );
+    assert_eq!(
+        Atom(Time(
+            chrono::NaiveTime::from_hms_milli_opt(12, 30, 1, 500).unwrap()
+        )),
+        parse_exprs_helper("@12:30:01.5")
+    );
+    assert_eq!(
+        Atom(Bytes(vec!(0xde, 0xad, 0xbe, 0xef))),
+        parse_exprs_helper("hex\"DEADbeef\"")
+    );
+    assert_eq!(
+        Atom(Bytes(vec!(0xde, 0xad, 0xbe, 0xef))),
+        parse_exprs_helper("base64\"3q2+7w==\"")
+    );
+    assert_eq!(Atom(Bytes(vec!())), parse_exprs_helper("hex\"\""));
+    assert_eq!(
+        Apply(
+            Box::new(Ident("hex".to_string())),
+            Box::new(Atom(String("00".to_string())))
+        ),
+        parse_exprs_helper("hex \"00\"")
+    );
+    assert_eq!(
+        parse_exprs_helper("@2026-10-17T10:30:00Z"),
+        parse_exprs_helper("@2026-10-17T12:30:00+02:00")
+    );
+    assert_eq!(
+        parse_exprs_helper("@2026-10-18T01:00:00Z"),
+        parse_exprs_helper("@2026-10-17T23:30:00-01:30")
+    );
+    assert_eq!(Ident("x".to_string()), parse_exprs_helper("x"));
     assert_eq!(
         Apply(
             Box::new(Ident("x".to_string())),
             Box::new(Ident("y".to_string()))
         ),
-        parse_exprs_helper(&"x y")
+        parse_exprs_helper("x y")
     );
     assert_eq!(
         Apply(
@@ -77,7 +154,7 @@ fn parse_exprs() {
             )),
             Box::new(Ident("z".to_string()))
         ),
-        parse_exprs_helper(&"x y z")
+        parse_exprs_helper("x y z")
     );
     assert_eq!(
         Apply(
@@ -87,7 +164,7 @@ fn parse_exprs() {
             )),
             Box::new(Ident("z".to_string()))
         ),
-        parse_exprs_helper(&"(x y) z")
+        parse_exprs_helper("(x y) z")
     );
     assert_eq!(
         Apply(
@@ -97,26 +174,159 @@ fn parse_exprs() {
                 Box::new(Ident("z".to_string()))
             ))
         ),
-        parse_exprs_helper(&"x (y z)")
+        parse_exprs_helper("x (y z)")
     );
     assert_eq!(
         Apply(
             Box::new(Ident("x".to_string())),
             Box::new(Tuple(vec!(Ident("y".to_string()), Ident("z".to_string()))))
         ),
-        parse_exprs_helper(&"x (y, z)")
+        parse_exprs_helper("x (y, z)")
     );
     assert_eq!(
-        Lambda("f".to_string(), Box::new(Ident("x".to_string()))),
-        parse_exprs_helper(&"lambda f -> x")
+        Lambda("f".to_string(), None, Box::new(Ident("x".to_string()))),
+        parse_exprs_helper("lambda f -> x")
     );
     assert_eq!(
         Let(
-            vec!(("x".to_string(), Atom(Int(42)))),
+            vec!((Pattern::Ident("x".to_string()), Atom(Int(42)))),
             Box::new(Ident("x".to_string()))
         ),
-        parse_exprs_helper(&"let x = 42 in x end")
+        parse_exprs_helper("let x = 42 in x end")
     );
+    assert_eq!(
+        If(
+            Box::new(Ident("b".to_string())),
+            Box::new(Atom(Int(1))),
+            Box::new(Atom(Int(2)))
+        ),
+        parse_exprs_helper("if b then 1 else 2")
+    );
+    assert_eq!(
+        Apply(
+            Box::new(Ident("index".to_string())),
+            Box::new(Ident("iffy".to_string()))
+        ),
+        parse_exprs_helper("index iffy")
+    );
+    assert_eq!(
+        Apply(
+            Box::new(Ident("to_upper".to_string())),
+            Box::new(Ident("let_x".to_string()))
+        ),
+        parse_exprs_helper("to_upper let_x")
+    );
+    assert_eq!(
+        Apply(
+            Box::new(Ident("f".to_string())),
+            Box::new(Field(
+                Box::new(Field(Box::new(Ident("r".to_string())), "x".to_string())),
+                "y".to_string()
+            ))
+        ),
+        parse_exprs_helper("f r.x.y")
+    );
+    assert_eq!(
+        Update(
+            Box::new(Ident("r".to_string())),
+            vec!(
+                ("x".to_string(), Atom(Int(1))),
+                ("y".to_string(), Atom(Int(2)))
+            )
+        ),
+        parse_exprs_helper("{ r with y = 2, x = 1 }")
+    );
+}
+
+#[test]
+fn parse_operators() {
+    use crate::ast::Atom::*;
+    use Expr::*;
+
+    assert_eq!(
+        Apply(
+            Box::new(Apply(
+                Box::new(Ident("+".to_string())),
+                Box::new(Atom(Int(1)))
+            )),
+            Box::new(Ident("x".to_string()))
+        ),
+        parse_exprs_helper("1 + x")
+    );
+    assert_eq!(Ident("<=".to_string()), parse_exprs_helper("(<=)"));
+
+    fn printed(input: &str) -> std::string::String {
+        format!("{}", parse_exprs_helper(input))
+    }
+
+    assert_eq!("1 + 2 * 3", printed("1 + (2 * 3)"));
+    assert_eq!("(1 + 2) * 3", printed("(1 + 2) * 3"));
+    assert_eq!("1 - 2 - 3", printed("(1 - 2) - 3"));
+    assert_eq!("1 - (2 - 3)", printed("1 - (2 - 3)"));
+    assert_eq!("f x % g y", printed("(f x) % (g y)"));
+    assert_eq!("x - 1 == y / 2", printed("x-1 == y/2"));
+    assert_eq!("x - 1 :: y :: []", printed("x - 1 :: y :: []"));
+    assert_eq!("(x :: xs) :: ys", printed("(x :: xs) :: ys"));
+    assert_eq!("f (-1)", printed("f (-1)"));
+    assert_eq!("-1 + -2", printed("-1 + -2"));
+    assert_eq!("(+) 1", printed("(+) 1"));
+    assert_eq!("(::) x xs", printed("(::) x xs"));
+}
+
+#[test]
+fn parse_literals_out_of_range() {
+    for input in &[
+        "1e400",
+        "9223372036854775808",
+        "1234567890123456789012345678901234567890d",
+        "@2026-02-30",
+        "@24:00",
+        "@2026-10-17T12:00:00+25:00",
+        "base64\"3q2+7\"",
+        "hex\"0\"",
+        "hex\"abc\"",
+        r#""\ud83d""#,
+        r#""\ude00""#,
+        r#""\ud83d\u0041""#,
+    ] {
+        assert!(super::parse_exprs(
+            Parser::parse(Rule::expr, input)
+                .unwrap()
+                .next()
+                .unwrap()
+                .into_inner()
+        )
+        .is_err());
+    }
+
+    // An odd number of hex digits is still a Bytes literal, not an identifier
+    assert!(format!("{}", parse("let x = hex\"0\"").unwrap_err())
+        .contains("Invalid Bytes literal: Odd number of digits"));
+}
+
+#[test]
+fn literal_params() {
+    // The only literal parameter is `()`, since the others are refutable
+    assert!(parse("let g 1 = 2").is_err());
+    assert!(parse("let f = lambda \"x\" -> 1").is_err());
+    assert!(parse("let g () = 2").is_ok());
+    assert_eq!(
+        "lambda (1) -> 2",
+        format!("{}", parse_exprs_helper("lambda (1) -> 2"))
+    );
+}
+
+#[test]
+fn malformed_let_is_an_error() {
+    for input in &[
+        "let x = let rec f = 1 in end",
+        "let x = let rec f = 1 and in f end",
+        "let x = let rec = 1 in 2 end",
+        "let x = let y = 1 in end",
+        "let x = let y in y end",
+    ] {
+        assert!(parse(input).is_err());
+    }
 }
 
 #[test]
@@ -130,6 +340,50 @@ fn parse_and_print_is_isomorph() {
     isomorph("let x = 42 in x end");
 
     isomorph("let id = lambda x -> x in id end");
+
+    isomorph("if b then 1 else 2");
+
+    isomorph("let rec f = lambda x -> g x and g = lambda x -> f x in f end");
+
+    isomorph("(f x).y.z");
+
+    isomorph("{ r with x = 1, y = r.y }");
+
+    isomorph("[1, [], x :: xs]");
+
+    isomorph("1 + x * 2 < f (-1)");
+
+    isomorph("[1.0, -0.5, 1e-7, 1.5e300, f (-2.0), x *. 0.1]");
+
+    isomorph("(12345678901234567890n, -1.50d, f (-1n), g (-0.1d))");
+
+    isomorph("[@2026-10-17, @12:30:00, @12:30:00.250, @2026-10-17T12:30:00Z]");
+
+    isomorph("@2026-10-17T12:30:00.123Z");
+
+    isomorph("(hex\"\", hex\"00ff\")");
+
+    isomorph(r#"["", "a\"b\\c", "line\nbreak\ttab", "\u0000\u001b", "æøå 😀"]"#);
+
+    isomorph("fold (+) 0 xs");
+
+    isomorph("(lambda x -> x) (if b then f else g) 1");
+
+    isomorph("(f x).y.z + (-1).x");
+
+    isomorph("case l of | [] => 0 | [x, _] => x | x :: xs => x end");
+
+    isomorph("let f : Int -> { x: Int } = lambda (n : Int) -> { x = (n : Int) } in f end");
+
+    isomorph("lambda (a, b) -> lambda { x, .. } -> lambda (c : Int) -> lambda _ -> lambda () -> (a, x, c)");
+
+    isomorph("let (a, _) = p in let { x = px, y } = q in (a, px, y) end end");
+
+    isomorph("case r of | { a, b = (x, _) } => x | { a = { c, .. }, .. } => c end");
+
+    isomorph("case l of | 0 | 1 as x => x | x :: _ when x > 0 => x | _ => 0 end");
+
+    isomorph("case o of | Some (Some x :: xs) => x | Some [] | None => 0 end");
 }
 
 #[test]
@@ -141,7 +395,7 @@ fn parse_insert() {
     assert_eq!(
         Insert("x".to_string(), Atom(Int(4))),
         super::parse_insert(
-            Parser::parse(Rule::insert, &"insert 4 into x")
+            Parser::parse(Rule::insert, "insert 4 into x")
                 .unwrap()
                 .next()
                 .unwrap()
@@ -155,7 +409,7 @@ fn parse_insert() {
             Apply(Box::new(Ident("f".to_string())), Box::new(Atom(Int(4))))
         ),
         super::parse_insert(
-            Parser::parse(Rule::insert, &"insert f 4 into x")
+            Parser::parse(Rule::insert, "insert f 4 into x")
                 .unwrap()
                 .next()
                 .unwrap()
@@ -221,16 +475,38 @@ fn parse_create_record() {
         Statement::Create(
             String::from("x"),
             TableDefinition {
-                ty: Ty::Record(vec!(
-                    (String::from("x"), Ty::Bool),
-                    (String::from("y"), Ty::Int)
-                ))
+                ty: Ty::Record(
+                    vec!((String::from("x"), Ty::Bool), (String::from("y"), Ty::Int)),
+                    None
+                )
             }
         ),
         parse("create table x { y : Int, x : Bool }").unwrap()
     );
 }
 
+#[test]
+fn parse_record_row() {
+    let ty = Ty::Record(vec![(String::from("x"), Ty::Int)], Some(String::from("r")));
+
+    assert_eq!(
+        Statement::Create(String::from("t"), TableDefinition { ty: ty.clone() }),
+        parse("create table t { x : Int, ..r }").unwrap()
+    );
+
+    assert_eq!("{ x: Int, ..r }", ty.to_string());
+
+    assert_eq!(
+        Statement::Create(
+            String::from("t"),
+            TableDefinition {
+                ty: Ty::Record(vec![], Some(String::from("r")))
+            }
+        ),
+        parse("create table t { ..r }").unwrap()
+    );
+}
+
 #[test]
 fn parse_insert_int() {
     assert_eq!(
@@ -336,9 +612,136 @@ fn parse_select() {
 #[test]
 fn parse_letdecl() {
     assert_eq!(
-        Statement::Let(String::from("x"), Expr::Atom(Atom::Int(42))),
+        Statement::Let(Pattern::Ident(String::from("x")), Expr::Atom(Atom::Int(42))),
         parse("let x = 42").unwrap()
     );
+
+    assert_eq!(
+        Statement::Let(
+            Pattern::Tuple(vec!(
+                Pattern::Ident(String::from("a")),
+                Pattern::Record(
+                    vec!((String::from("x"), Pattern::Ident(String::from("px")))),
+                    true
+                )
+            )),
+            Expr::Ident(String::from("p"))
+        ),
+        parse("let (a, { x = px, .. }) = p").unwrap()
+    );
+
+    assert_eq!(
+        Statement::Let(
+            Pattern::Ident(String::from("f")),
+            Expr::Annot(
+                Box::new(Expr::Lambda(
+                    String::from("x"),
+                    Some(Ty::Tuple(vec!(Ty::Int, Ty::Var(String::from("a"))))),
+                    Box::new(Expr::Annot(
+                        Box::new(Expr::Ident(String::from("x"))),
+                        Ty::Tuple(vec!(Ty::Int, Ty::Var(String::from("a"))))
+                    ))
+                )),
+                Ty::Fun(
+                    Box::new(Ty::Tuple(vec!(Ty::Int, Ty::Var(String::from("a"))))),
+                    Box::new(Ty::Tuple(vec!(Ty::Int, Ty::Var(String::from("a")))))
+                )
+            )
+        ),
+        parse("let f : (Int, a) -> (Int, a) = lambda (x : (Int, a)) -> (x : (Int, a))").unwrap()
+    );
+}
+
+#[test]
+fn parse_function_sugar() {
+    use crate::ast::LAMBDA_ARG;
+
+    fn lambda(ident: &str, body: Expr) -> Expr {
+        Expr::Lambda(ident.to_string(), None, Box::new(body))
+    }
+
+    assert_eq!(
+        Statement::Let(
+            Pattern::Ident(String::from("f")),
+            lambda("x", lambda("y", Expr::Ident(String::from("x"))))
+        ),
+        parse("let f x y = x").unwrap()
+    );
+
+    assert_eq!(parse("let f x y = x"), parse("let f = lambda x y -> x"));
+
+    assert_eq!(
+        parse("let f = lambda x -> lambda y -> x"),
+        parse("let f = lambda x y -> x")
+    );
+
+    assert_eq!(
+        Statement::Let(
+            Pattern::Ident(String::from("fst")),
+            lambda(
+                LAMBDA_ARG,
+                Expr::Let(
+                    vec!((
+                        Pattern::Tuple(vec!(Pattern::Ident(String::from("a")), Pattern::Wildcard)),
+                        Expr::Ident(LAMBDA_ARG.to_string())
+                    )),
+                    Box::new(Expr::Ident(String::from("a")))
+                )
+            )
+        ),
+        parse("let fst (a, _) = a").unwrap()
+    );
+
+    assert_eq!(
+        parse("let rec f = lambda x -> g x and g = lambda y -> y").unwrap(),
+        parse("let rec f x = g x and g y = y").unwrap()
+    );
+}
+
+#[test]
+fn parse_letrecdecl() {
+    assert_eq!(
+        Statement::LetRec(vec!(
+            (
+                String::from("f"),
+                Expr::Lambda(
+                    String::from("x"),
+                    None,
+                    Box::new(Expr::Apply(
+                        Box::new(Expr::Ident(String::from("g"))),
+                        Box::new(Expr::Ident(String::from("x")))
+                    ))
+                )
+            ),
+            (
+                String::from("g"),
+                Expr::Lambda(
+                    String::from("y"),
+                    None,
+                    Box::new(Expr::Ident(String::from("y")))
+                )
+            )
+        )),
+        parse("let rec f = lambda x -> g x and g = lambda y -> y").unwrap()
+    );
+
+    assert_eq!(
+        Statement::Let(
+            Pattern::Ident(String::from("x")),
+            Expr::LetRec(
+                vec!((
+                    String::from("f"),
+                    Expr::Lambda(
+                        String::from("y"),
+                        None,
+                        Box::new(Expr::Ident(String::from("y")))
+                    )
+                )),
+                Box::new(Expr::Ident(String::from("f")))
+            )
+        ),
+        parse("let x = let rec f = lambda y -> y in f end").unwrap()
+    );
 }
 
 #[test]
@@ -347,11 +750,12 @@ fn parse_case() {
 
     assert_eq!(
         Statement::Let(
-            String::from("x"),
+            Pattern::Ident(String::from("x")),
             Expr::Case(
                 Box::new(Expr::Atom(Atom::Int(42))),
                 vec!((
                     Pattern::Ident("i".to_string()),
+                    None,
                     Expr::Ident("i".to_string())
                 ))
             )
@@ -361,7 +765,7 @@ fn parse_case() {
 
     assert_eq!(
         Statement::Let(
-            String::from("x"),
+            Pattern::Ident(String::from("x")),
             Expr::Case(
                 Box::new(Expr::Atom(Atom::Int(42))),
                 vec!((
@@ -369,6 +773,7 @@ fn parse_case() {
                         Pattern::Ident("i".to_string()),
                         Pattern::Ident("j".to_string())
                     )),
+                    None,
                     Expr::Ident("j".to_string())
                 ))
             )
@@ -378,10 +783,10 @@ fn parse_case() {
 
     assert_eq!(
         Statement::Let(
-            String::from("x"),
+            Pattern::Ident(String::from("x")),
             Expr::Case(
                 Box::new(Expr::Atom(Atom::Int(42))),
-                vec!((Pattern::Wildcard, Expr::Ident("j".to_string())))
+                vec!((Pattern::Wildcard, None, Expr::Ident("j".to_string())))
             )
         ),
         parse("let x = case 42 of _ => j end").unwrap()
@@ -389,38 +794,63 @@ fn parse_case() {
 
     assert_eq!(
         Statement::Let(
-            String::from("x"),
+            Pattern::Ident(String::from("x")),
             Expr::Case(
                 Box::new(Expr::Atom(Atom::Int(42))),
                 vec!((
-                    Pattern::Record(vec!(
-                        (String::from("i"), Pattern::Wildcard),
-                        (String::from("j"), Pattern::Ident("j".to_string()))
-                    )),
+                    Pattern::Record(
+                        vec!(
+                            (String::from("i"), Pattern::Wildcard),
+                            (String::from("j"), Pattern::Ident("j".to_string()))
+                        ),
+                        false
+                    ),
+                    None,
                     Expr::Ident("j".to_string())
                 ))
             )
         ),
         parse("let x = case 42 of { i = _, j = j } => j end").unwrap()
     );
+
+    assert_eq!(
+        Statement::Let(
+            Pattern::Ident(String::from("x")),
+            Expr::Case(
+                Box::new(Expr::Ident("r".to_string())),
+                vec!((
+                    Pattern::Record(
+                        vec!(
+                            (String::from("age"), Pattern::Atom(Atom::Int(42))),
+                            (String::from("name"), Pattern::Ident("name".to_string()))
+                        ),
+                        true
+                    ),
+                    None,
+                    Expr::Ident("name".to_string())
+                ))
+            )
+        ),
+        parse("let x = case r of { name, age = 42, .. } => name end").unwrap()
+    );
 }
 
 #[test]
 fn parse_typedecl() {
     assert_eq!(
-        Statement::Union(
+        Statement::Union(vec!((
             String::from("Option"),
             vec!(),
             vec!(
                 ("Some".to_string(), vec!(Ty::Int)),
                 ("Nothing".to_string(), vec!())
             )
-        ),
+        ))),
         parse("type Option = Some Int | Nothing").unwrap()
     );
 
     assert_eq!(
-        Statement::Union(
+        Statement::Union(vec!((
             String::from("List"),
             vec!(),
             vec!(
@@ -430,12 +860,12 @@ fn parse_typedecl() {
                 ),
                 ("Nil".to_string(), vec!())
             )
-        ),
+        ))),
         parse("type List = Cons Int List | Nil").unwrap()
     );
 
     assert_eq!(
-        Statement::Union(
+        Statement::Union(vec!((
             String::from("List"),
             vec!(String::from("a")),
             vec!(
@@ -448,7 +878,424 @@ fn parse_typedecl() {
                 ),
                 ("Nil".to_string(), vec!())
             )
-        ),
+        ))),
         parse("type List a = Cons a List | Nil").unwrap()
     );
+
+    assert_eq!(
+        Statement::Union(vec!((
+            String::from("Seq"),
+            vec!(String::from("a")),
+            vec!(
+                ("Nil".to_string(), vec!()),
+                (
+                    "Cons".to_string(),
+                    vec!(
+                        Ty::Var(String::from("a")),
+                        Ty::Defined("Seq".to_string(), vec!(Ty::Var(String::from("a"))))
+                    )
+                )
+            )
+        ))),
+        parse("type Seq a = Nil | Cons a (Seq a)").unwrap()
+    );
+
+    assert_eq!(
+        Statement::Union(vec!(
+            (
+                String::from("Tree"),
+                vec!(),
+                vec!(
+                    ("Leaf".to_string(), vec!()),
+                    (
+                        "Node".to_string(),
+                        vec!(Ty::Defined("Forest".to_string(), vec!()))
+                    )
+                )
+            ),
+            (
+                String::from("Forest"),
+                vec!(),
+                vec!(
+                    ("Empty".to_string(), vec!()),
+                    (
+                        "Trees".to_string(),
+                        vec!(
+                            Ty::Defined("Tree".to_string(), vec!()),
+                            Ty::Defined("Forest".to_string(), vec!())
+                        )
+                    )
+                )
+            )
+        )),
+        parse("type Tree = Leaf | Node Forest and Forest = Empty | Trees Tree Forest").unwrap()
+    );
+}
+
+#[test]
+fn parse_alias_and_newtype() {
+    assert_eq!(
+        Statement::Alias("Email".to_string(), vec!(), Ty::String),
+        parse("type alias Email = String").unwrap()
+    );
+
+    assert_eq!(
+        Statement::Alias(
+            "Pair".to_string(),
+            vec!("a".to_string()),
+            Ty::Tuple(vec!(Ty::Var("a".to_string()), Ty::Var("a".to_string())))
+        ),
+        parse("type alias Pair a = (a, a)").unwrap()
+    );
+
+    assert_eq!(
+        Statement::Newtype("UserId".to_string(), vec!(), "UserId".to_string(), Ty::Int),
+        parse("newtype UserId = UserId Int").unwrap()
+    );
+
+    assert_eq!(
+        Statement::Newtype(
+            "Wrap".to_string(),
+            vec!("a".to_string()),
+            "MkWrap".to_string(),
+            Ty::List(Box::new(Ty::Var("a".to_string())))
+        ),
+        parse("newtype Wrap a = MkWrap [a]").unwrap()
+    );
+}
+
+#[test]
+fn parse_typeof() {
+    assert_eq!(
+        Statement::TypeOf(Expr::Apply(
+            Box::new(Expr::Ident("f".to_string())),
+            Box::new(Expr::Atom(Atom::Int(1)))
+        )),
+        parse("typeof f 1").unwrap()
+    );
+
+    assert_eq!(
+        Statement::TypeOfTable("users".to_string()),
+        parse("typeof table users").unwrap()
+    );
+
+    // `table` is only special when followed by the name of the table
+    assert_eq!(
+        Statement::TypeOf(Expr::Ident("tables".to_string())),
+        parse("typeof tables").unwrap()
+    );
+
+    assert_eq!(
+        "typeof table users",
+        format!("{}", parse("typeof table users").unwrap())
+    );
+}
+
+#[test]
+fn parse_constructor_pattern() {
+    assert_eq!(
+        Statement::Let(
+            Pattern::Ident(String::from("x")),
+            Expr::Case(
+                Box::new(Expr::Ident("l".to_string())),
+                vec!(
+                    (
+                        Pattern::Constructor(
+                            "Cons".to_string(),
+                            vec!(
+                                Pattern::Ident("x".to_string()),
+                                Pattern::Constructor(
+                                    "Cons".to_string(),
+                                    vec!(
+                                        Pattern::Wildcard,
+                                        Pattern::Constructor("Nil".to_string(), vec!())
+                                    )
+                                )
+                            )
+                        ),
+                        None,
+                        Expr::Ident("x".to_string())
+                    ),
+                    (
+                        Pattern::Constructor("Nil".to_string(), vec!()),
+                        None,
+                        Expr::Atom(Atom::Int(0))
+                    )
+                )
+            )
+        ),
+        parse("let x = case l of Cons x (Cons _ Nil) => x | Nil => 0 end").unwrap()
+    );
+}
+
+#[test]
+fn parse_or_as_and_guard_patterns() {
+    assert_eq!(
+        Statement::Let(
+            Pattern::Ident(String::from("x")),
+            Expr::Case(
+                Box::new(Expr::Ident("l".to_string())),
+                vec!(
+                    (
+                        Pattern::As(
+                            Box::new(Pattern::Or(vec!(
+                                Pattern::Constructor(
+                                    "Some".to_string(),
+                                    vec!(Pattern::Atom(Atom::Int(0)))
+                                ),
+                                Pattern::Constructor("None".to_string(), vec!())
+                            ))),
+                            "y".to_string()
+                        ),
+                        None,
+                        Expr::Ident("y".to_string())
+                    ),
+                    (
+                        Pattern::Constructor(
+                            "Some".to_string(),
+                            vec!(Pattern::Ident("n".to_string()))
+                        ),
+                        Some(Expr::Apply(
+                            Box::new(Expr::Apply(
+                                Box::new(Expr::Ident(">".to_string())),
+                                Box::new(Expr::Ident("n".to_string()))
+                            )),
+                            Box::new(Expr::Atom(Atom::Int(0)))
+                        )),
+                        Expr::Ident("l".to_string())
+                    )
+                )
+            )
+        ),
+        parse("let x = case l of | Some 0 | None as y => y | Some n when n > 0 => l end").unwrap()
+    );
+
+    assert_eq!(
+        Statement::Let(
+            Pattern::Ident(String::from("x")),
+            Expr::Case(
+                Box::new(Expr::Ident("l".to_string())),
+                vec!((
+                    Pattern::Cons(
+                        Box::new(Pattern::Or(vec!(
+                            Pattern::Atom(Atom::Int(1)),
+                            Pattern::Atom(Atom::Int(2))
+                        ))),
+                        Box::new(Pattern::Wildcard)
+                    ),
+                    None,
+                    Expr::Atom(Atom::Int(0))
+                ))
+            )
+        ),
+        parse("let x = case l of (1 | 2) :: _ => 0 end").unwrap()
+    );
+}
+
+#[test]
+fn print_typedecl() {
+    for input in &[
+        "type Seq a = Nil | Cons a (Seq a)",
+        "type Tree = Leaf | Node Forest and Forest = Empty | Trees Tree Forest",
+        "type alias Pair a = (a, a)",
+        "newtype Wrap a = Wrap (Option a)",
+    ] {
+        assert_eq!(*input, format!("{}", parse(input).unwrap()));
+    }
+}
+
+/// Parse all of `input` as `rule`
+fn parse_all(rule: Rule, input: &str) -> Pair<'_, Rule> {
+    let pair = Parser::parse(rule, input)
+        .unwrap_or_else(|e| panic!("{}\n{}", input, e))
+        .next()
+        .unwrap();
+    assert_eq!(input, pair.as_str());
+    pair
+}
+
+fn arb_ident() -> impl Strategy<Value = Ident> {
+    prop::sample::select(vec!["x", "y", "xs", "foo_1"]).prop_map(|s| s.to_string())
+}
+
+fn arb_tyident() -> impl Strategy<Value = Ident> {
+    prop::sample::select(vec!["Some", "None", "Pair"]).prop_map(|s| s.to_string())
+}
+
+fn arb_ty() -> impl Strategy<Value = Ty> {
+    let leaf = prop_oneof![
+        Just(Ty::Int),
+        Just(Ty::Float),
+        Just(Ty::Decimal),
+        Just(Ty::Bool),
+        Just(Ty::String),
+        Just(Ty::Unit),
+        arb_ident().prop_map(Ty::Var),
+        arb_tyident().prop_map(|name| Ty::Defined(name, vec![])),
+    ];
+
+    leaf.prop_recursive(4, 32, 3, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 2..4).prop_map(Ty::Tuple),
+            (
+                prop::collection::btree_map(arb_ident(), inner.clone(), 0..3),
+                prop::option::of(arb_ident())
+            )
+                .prop_filter("records have fields or a row", |(fields, row)| {
+                    !fields.is_empty() || row.is_some()
+                })
+                .prop_map(|(fields, row)| Ty::Record(fields.into_iter().collect(), row)),
+            (arb_tyident(), prop::collection::vec(inner.clone(), 1..3))
+                .prop_map(|(name, args)| Ty::Defined(name, args)),
+            (inner.clone(), inner.clone()).prop_map(|(a, b)| Ty::Fun(Box::new(a), Box::new(b))),
+            inner.prop_map(|ty| Ty::List(Box::new(ty))),
+        ]
+    })
+}
+
+fn arb_atom() -> impl Strategy<Value = Atom> {
+    prop_oneof![
+        Just(Atom::Unit),
+        any::<bool>().prop_map(Atom::Bool),
+        any::<i64>().prop_map(Atom::Int),
+        any::<i64>().prop_map(|i| Atom::BigInt(i.into())),
+        (any::<i32>(), 0..4u32)
+            .prop_map(|(i, scale)| Atom::Decimal(rust_decimal::Decimal::new(i.into(), scale))),
+        "[a-z\"\\\\\n]{0,4}".prop_map(Atom::String),
+    ]
+}
+
+fn arb_pattern() -> impl Strategy<Value = Pattern> {
+    let leaf = prop_oneof![
+        arb_atom().prop_map(Pattern::Atom),
+        Just(Pattern::Wildcard),
+        arb_ident().prop_map(Pattern::Ident),
+        arb_tyident().prop_map(|name| Pattern::Constructor(name, vec![])),
+    ];
+
+    leaf.prop_recursive(4, 32, 3, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 2..4).prop_map(Pattern::Tuple),
+            (
+                prop::collection::btree_map(arb_ident(), inner.clone(), 1..3),
+                any::<bool>()
+            )
+                .prop_map(|(fields, open)| Pattern::Record(fields.into_iter().collect(), open)),
+            (arb_tyident(), prop::collection::vec(inner.clone(), 1..3))
+                .prop_map(|(name, args)| Pattern::Constructor(name, args)),
+            prop::collection::vec(inner.clone(), 0..3).prop_map(Pattern::List),
+            (inner.clone(), inner.clone())
+                .prop_map(|(head, tail)| Pattern::Cons(Box::new(head), Box::new(tail))),
+            prop::collection::vec(inner.clone(), 2..4).prop_map(Pattern::Or),
+            (inner, arb_ident()).prop_map(|(pat, name)| Pattern::As(Box::new(pat), name)),
+        ]
+    })
+}
+
+fn arb_expr() -> impl Strategy<Value = Expr> {
+    let leaf = prop_oneof![
+        arb_atom().prop_map(Expr::Atom),
+        any::<f64>()
+            .prop_filter("floats are finite", |x| x.is_finite())
+            .prop_map(|x| Expr::Atom(Atom::Float(x))),
+        arb_ident().prop_map(Expr::Ident),
+        arb_tyident().prop_map(Expr::Ident),
+        prop::sample::select(vec!["+", "-.", "::", "<="])
+            .prop_map(|op| Expr::Ident(op.to_string())),
+    ];
+
+    leaf.prop_recursive(4, 48, 3, |inner| {
+        let fields = |inner| {
+            prop::collection::btree_map(arb_ident(), inner, 1..3)
+                .prop_map(|fields| fields.into_iter().collect::<Vec<_>>())
+        };
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 2..4).prop_map(Expr::Tuple),
+            prop::collection::vec(inner.clone(), 0..3).prop_map(Expr::List),
+            (inner.clone(), inner.clone())
+                .prop_map(|(head, tail)| Expr::Cons(Box::new(head), Box::new(tail))),
+            fields(inner.clone()).prop_map(Expr::Record),
+            (arb_pattern(), inner.clone(), inner.clone())
+                .prop_map(|(pat, e, body)| Expr::Let(vec![(pat, e)], Box::new(body))),
+            (
+                prop::collection::vec((arb_ident(), inner.clone()), 1..3),
+                inner.clone()
+            )
+                .prop_map(|(binds, body)| Expr::LetRec(binds, Box::new(body))),
+            (inner.clone(), inner.clone())
+                .prop_map(|(e1, e2)| Expr::Apply(Box::new(e1), Box::new(e2))),
+            (
+                prop::sample::select(vec!["==", "<", "+", "-", "*", "/", "%", "+.", "*."]),
+                inner.clone(),
+                inner.clone()
+            )
+                .prop_map(|(op, lhs, rhs)| Expr::Apply(
+                    Box::new(Expr::Apply(
+                        Box::new(Expr::Ident(op.to_string())),
+                        Box::new(lhs)
+                    )),
+                    Box::new(rhs)
+                )),
+            (arb_ident(), prop::option::of(arb_ty()), inner.clone())
+                .prop_map(|(x, ty, body)| Expr::Lambda(x, ty, Box::new(body))),
+            (
+                arb_pattern().prop_filter("identifiers are plain parameters", |pat| {
+                    !matches!(pat, Pattern::Ident(_))
+                }),
+                inner.clone()
+            )
+                .prop_map(|(pat, body)| Expr::Lambda(
+                    LAMBDA_ARG.to_string(),
+                    None,
+                    Box::new(Expr::Let(
+                        vec![(pat, Expr::Ident(LAMBDA_ARG.to_string()))],
+                        Box::new(body)
+                    ))
+                )),
+            (
+                inner.clone(),
+                prop::collection::vec(
+                    (
+                        arb_pattern(),
+                        prop::option::of(inner.clone()),
+                        inner.clone()
+                    ),
+                    1..3
+                )
+            )
+                .prop_map(|(e, arms)| Expr::Case(Box::new(e), arms)),
+            (inner.clone(), inner.clone(), inner.clone()).prop_map(|(cond, then, els)| Expr::If(
+                Box::new(cond),
+                Box::new(then),
+                Box::new(els)
+            )),
+            (inner.clone(), arb_ident()).prop_map(|(e, field)| Expr::Field(Box::new(e), field)),
+            (inner.clone(), fields(inner.clone()))
+                .prop_map(|(e, fields)| Expr::Update(Box::new(e), fields)),
+            (inner, arb_ty()).prop_map(|(e, ty)| Expr::Annot(Box::new(e), ty)),
+        ]
+    })
+}
+
+proptest! {
+    #[test]
+    fn print_and_parse_ty(ty in arb_ty()) {
+        let printed = ty.to_string();
+        let pair = parse_all(Rule::ty, &printed);
+        prop_assert_eq!(ty, super::parse_ty(pair.into_inner()).unwrap());
+    }
+
+    #[test]
+    fn print_and_parse_pattern(pat in arb_pattern()) {
+        let printed = pat.to_string();
+        let pair = parse_all(Rule::pattern, &printed);
+        prop_assert_eq!(pat, parse_pat(pair.into_inner()).unwrap());
+    }
+
+    #[test]
+    fn print_and_parse_expr(expr in arb_expr()) {
+        let printed = expr.to_string();
+        let pair = parse_all(Rule::expr, &printed);
+        prop_assert_eq!(expr, super::parse_exprs(pair.into_inner()).unwrap());
+    }
 }
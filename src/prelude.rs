@@ -0,0 +1,725 @@
+use crate::ast::{fmt_string, Ident};
+use crate::object::Object;
+use crate::parse::{parse_ty, Parser, Rule};
+use crate::ty::{self, Class, Scheme};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Utc};
+use num_bigint::BigInt;
+use pest::Parser as _;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::rc::Rc;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+#[cfg(test)]
+mod test;
+
+/// A built-in function: its name, its declared type scheme and its value
+pub type Builtin = (Ident, Scheme, Object);
+
+/// All built-in functions, which are loaded into the environments of the
+/// database at startup.
+pub fn builtins() -> Vec<Builtin> {
+    vec![
+        // Arithmetic
+        builtin(
+            "+",
+            "Num a => a -> a -> a",
+            num(
+                i64::checked_add,
+                |x, y| x + y,
+                |x, y| Some(x + y),
+                Decimal::checked_add,
+            ),
+        ),
+        builtin(
+            "-",
+            "Num a => a -> a -> a",
+            num(
+                i64::checked_sub,
+                |x, y| x - y,
+                |x, y| Some(x - y),
+                Decimal::checked_sub,
+            ),
+        ),
+        builtin(
+            "*",
+            "Num a => a -> a -> a",
+            num(
+                i64::checked_mul,
+                |x, y| x * y,
+                |x, y| Some(x * y),
+                Decimal::checked_mul,
+            ),
+        ),
+        builtin(
+            "/",
+            "Num a => a -> a -> a",
+            num(
+                i64::checked_div,
+                |x, y| x / y,
+                bigint_div,
+                Decimal::checked_div,
+            ),
+        ),
+        builtin("%", "Int -> Int -> Int", arith(i64::checked_rem)),
+        builtin("+.", "Float -> Float -> Float", float_arith(|x, y| x + y)),
+        builtin("-.", "Float -> Float -> Float", float_arith(|x, y| x - y)),
+        builtin("*.", "Float -> Float -> Float", float_arith(|x, y| x * y)),
+        builtin("/.", "Float -> Float -> Float", float_arith(|x, y| x / y)),
+        builtin(
+            "sqrt",
+            "Float -> Float",
+            fun1(|x| Ok(Object::Float(float(x).sqrt()))),
+        ),
+        // Comparison
+        builtin("==", "Eq a => a -> a -> Bool", equality(|eq| eq)),
+        builtin("!=", "Eq a => a -> a -> Bool", equality(|eq| !eq)),
+        builtin(
+            "<",
+            "Ord a => a -> a -> Bool",
+            comparison(|o| o == Ordering::Less),
+        ),
+        builtin(
+            "<=",
+            "Ord a => a -> a -> Bool",
+            comparison(|o| o != Ordering::Greater),
+        ),
+        builtin(
+            ">",
+            "Ord a => a -> a -> Bool",
+            comparison(|o| o == Ordering::Greater),
+        ),
+        builtin(
+            ">=",
+            "Ord a => a -> a -> Bool",
+            comparison(|o| o != Ordering::Less),
+        ),
+        builtin(
+            "compare",
+            "Ord a => a -> a -> Int",
+            fun2(|x, y| {
+                Ok(Object::Int(match compare(&x, &y)? {
+                    Ordering::Less => -1,
+                    Ordering::Equal => 0,
+                    Ordering::Greater => 1,
+                }))
+            }),
+        ),
+        builtin(
+            "not",
+            "Bool -> Bool",
+            fun1(|b| match b {
+                Object::Bool(b) => Ok(Object::Bool(!b)),
+                other => unreachable!("{}", other),
+            }),
+        ),
+        // BigInt and Decimal
+        builtin(
+            "round_decimal",
+            "Int -> Decimal -> Decimal",
+            fun2(|places, d| match u32::try_from(int(places)) {
+                Ok(places) => Ok(Object::Decimal(
+                    decimal(d)
+                        .round_dp_with_strategy(places, RoundingStrategy::MidpointAwayFromZero),
+                )),
+                Err(_) => Err(anyhow!("Cannot round to a negative number of places")),
+            }),
+        ),
+        // Bytes
+        builtin(
+            "bytes_length",
+            "Bytes -> Int",
+            fun1(|b| Ok(Object::Int(bytes(b).len() as i64))),
+        ),
+        builtin(
+            "slice_bytes",
+            "Bytes -> Int -> Int -> Bytes",
+            fun3(|b, start, len| {
+                let b = bytes(b);
+                let (start, len) = (int(start), int(len));
+                match (usize::try_from(start), usize::try_from(len)) {
+                    (Ok(start), Ok(len)) if start.saturating_add(len) <= b.len() => {
+                        Ok(Object::Bytes(b[start..start + len].to_vec()))
+                    }
+                    _ => Err(anyhow!(
+                        "Slice from {} of length {} is out of bounds for {} bytes",
+                        start,
+                        len,
+                        b.len()
+                    )),
+                }
+            }),
+        ),
+        builtin(
+            "append_bytes",
+            "Bytes -> Bytes -> Bytes",
+            fun2(|b1, b2| {
+                let mut b1 = bytes(b1);
+                b1.extend(bytes(b2));
+                Ok(Object::Bytes(b1))
+            }),
+        ),
+        builtin(
+            "string_to_bytes",
+            "String -> Bytes",
+            fun1(|s| Ok(Object::Bytes(string(s).into_bytes()))),
+        ),
+        builtin(
+            "bytes_to_string",
+            "Bytes -> String",
+            fun1(|b| {
+                String::from_utf8(bytes(b))
+                    .map(Object::String)
+                    .map_err(|e| anyhow!("Bytes are not valid UTF-8: {}", e))
+            }),
+        ),
+        builtin(
+            "to_hex",
+            "Bytes -> String",
+            fun1(|b| Ok(Object::String(hex::encode(bytes(b))))),
+        ),
+        builtin(
+            "to_base64",
+            "Bytes -> String",
+            fun1(|b| Ok(Object::String(base64::encode(bytes(b))))),
+        ),
+        // Dates and times
+        builtin(
+            "add_days",
+            "Int -> Date -> Date",
+            fun2(|days, d| {
+                let (days, d) = (int(days), date(d));
+                Duration::try_days(days)
+                    .and_then(|days| d.checked_add_signed(days))
+                    .map(Object::Date)
+                    .ok_or_else(|| anyhow!("Adding {} days to {} is out of range", days, d))
+            }),
+        ),
+        builtin(
+            "days_between",
+            "Date -> Date -> Int",
+            fun2(|from, to| Ok(Object::Int((date(to) - date(from)).num_days()))),
+        ),
+        builtin(
+            "add_seconds",
+            "Int -> Timestamp -> Timestamp",
+            fun2(|secs, ts| {
+                let (secs, ts) = (int(secs), timestamp(ts));
+                Duration::try_seconds(secs)
+                    .and_then(|secs| ts.checked_add_signed(secs))
+                    .map(Object::Timestamp)
+                    .ok_or_else(|| anyhow!("Adding {} seconds to {} is out of range", secs, ts))
+            }),
+        ),
+        builtin(
+            "seconds_between",
+            "Timestamp -> Timestamp -> Int",
+            fun2(|from, to| Ok(Object::Int((timestamp(to) - timestamp(from)).num_seconds()))),
+        ),
+        builtin(
+            "year",
+            "Date -> Int",
+            fun1(|d| Ok(Object::Int(date(d).year() as i64))),
+        ),
+        builtin(
+            "month",
+            "Date -> Int",
+            fun1(|d| Ok(Object::Int(date(d).month() as i64))),
+        ),
+        builtin(
+            "day",
+            "Date -> Int",
+            fun1(|d| Ok(Object::Int(date(d).day() as i64))),
+        ),
+        builtin(
+            "date_of",
+            "Timestamp -> Date",
+            fun1(|ts| Ok(Object::Date(timestamp(ts).date_naive()))),
+        ),
+        builtin(
+            "time_of",
+            "Timestamp -> Time",
+            fun1(|ts| Ok(Object::Time(timestamp(ts).time()))),
+        ),
+        // A timestamp from a date and time in UTC
+        builtin(
+            "to_timestamp",
+            "Date -> Time -> Timestamp",
+            fun2(|d, t| Ok(Object::Timestamp(date(d).and_time(time(t)).and_utc()))),
+        ),
+        builtin(
+            "now",
+            "() -> Timestamp",
+            fun1(|_| Ok(Object::Timestamp(Utc::now()))),
+        ),
+        // Conversions
+        builtin(
+            "int_to_float",
+            "Int -> Float",
+            fun1(|i| Ok(Object::Float(int(i) as f64))),
+        ),
+        builtin(
+            "truncate",
+            "Float -> Int",
+            fun1(|x| float_to_int(float(x).trunc())),
+        ),
+        builtin(
+            "round",
+            "Float -> Int",
+            fun1(|x| float_to_int(float(x).round())),
+        ),
+        builtin(
+            "int_to_bigint",
+            "Int -> BigInt",
+            fun1(|i| Ok(Object::BigInt(BigInt::from(int(i))))),
+        ),
+        builtin(
+            "bigint_to_int",
+            "BigInt -> Int",
+            fun1(|i| {
+                let i = bigint(i);
+                i64::try_from(&i)
+                    .map(Object::Int)
+                    .map_err(|_| anyhow!("{} is out of range for an Int", i))
+            }),
+        ),
+        builtin(
+            "int_to_decimal",
+            "Int -> Decimal",
+            fun1(|i| Ok(Object::Decimal(Decimal::from(int(i))))),
+        ),
+        // Truncates towards zero, like `truncate`
+        builtin(
+            "decimal_to_int",
+            "Decimal -> Int",
+            fun1(|d| {
+                let d = decimal(d);
+                d.trunc()
+                    .to_i64()
+                    .map(Object::Int)
+                    .ok_or_else(|| anyhow!("{} is out of range for an Int", d))
+            }),
+        ),
+        builtin(
+            "bigint_to_decimal",
+            "BigInt -> Decimal",
+            fun1(|i| {
+                let i = bigint(i);
+                i.to_string()
+                    .parse()
+                    .map(Object::Decimal)
+                    .map_err(|_| anyhow!("{} is out of range for a Decimal", i))
+            }),
+        ),
+        builtin(
+            "float_to_decimal",
+            "Float -> Decimal",
+            fun1(|x| {
+                let x = float(x);
+                Decimal::from_f64(x)
+                    .map(Object::Decimal)
+                    .ok_or_else(|| anyhow!("{} is out of range for a Decimal", x))
+            }),
+        ),
+        builtin(
+            "decimal_to_float",
+            "Decimal -> Float",
+            fun1(|d| Ok(Object::Float(decimal(d).to_f64().unwrap()))),
+        ),
+        // The string representation of a value, as it is printed
+        builtin(
+            "show",
+            "Show a => a -> String",
+            fun1(|x| Ok(Object::String(x.to_string()))),
+        ),
+        // Strings. Lengths and offsets count grapheme clusters, i.e. what a
+        // reader would consider a single character.
+        builtin(
+            "string_length",
+            "String -> Int",
+            fun1(|s| Ok(Object::Int(string(s).graphemes(true).count() as i64))),
+        ),
+        builtin(
+            "concat",
+            "String -> String -> String",
+            fun2(|s1, s2| Ok(Object::String(string(s1) + &string(s2)))),
+        ),
+        builtin(
+            "substring",
+            "String -> Int -> Int -> String",
+            fun3(|s, start, len| {
+                let s = string(s);
+                let (start, len) = (int(start), int(len));
+                match (usize::try_from(start), usize::try_from(len)) {
+                    (Ok(start), Ok(len)) if start + len <= s.graphemes(true).count() => Ok(
+                        Object::String(s.graphemes(true).skip(start).take(len).collect()),
+                    ),
+                    _ => Err(anyhow!(
+                        "Substring from {} of length {} is out of bounds for {}",
+                        start,
+                        len,
+                        fmt_string(&s)
+                    )),
+                }
+            }),
+        ),
+        builtin(
+            "to_upper",
+            "String -> String",
+            fun1(|s| Ok(Object::String(string(s).to_uppercase()))),
+        ),
+        builtin(
+            "to_lower",
+            "String -> String",
+            fun1(|s| Ok(Object::String(string(s).to_lowercase()))),
+        ),
+        // For caseless comparisons, e.g. "Straße" and "STRASSE" fold to the
+        // same string
+        builtin(
+            "case_fold",
+            "String -> String",
+            fun1(|s| Ok(Object::String(caseless::default_case_fold_str(&string(s))))),
+        ),
+        builtin(
+            "normalize_nfc",
+            "String -> String",
+            fun1(|s| Ok(Object::String(string(s).nfc().collect()))),
+        ),
+        builtin(
+            "normalize_nfd",
+            "String -> String",
+            fun1(|s| Ok(Object::String(string(s).nfd().collect()))),
+        ),
+        builtin(
+            "normalize_nfkc",
+            "String -> String",
+            fun1(|s| Ok(Object::String(string(s).nfkc().collect()))),
+        ),
+        builtin(
+            "normalize_nfkd",
+            "String -> String",
+            fun1(|s| Ok(Object::String(string(s).nfkd().collect()))),
+        ),
+        builtin(
+            "int_to_string",
+            "Int -> String",
+            fun1(|i| Ok(Object::String(int(i).to_string()))),
+        ),
+        builtin(
+            "parse_int",
+            "String -> Int",
+            fun1(|s| {
+                let s = string(s);
+                s.parse()
+                    .map(Object::Int)
+                    .map_err(|e| anyhow!("Could not parse {} as an Int: {}", fmt_string(&s), e))
+            }),
+        ),
+        builtin(
+            "float_to_string",
+            "Float -> String",
+            fun1(|x| Ok(Object::String(format!("{:?}", float(x))))),
+        ),
+        builtin(
+            "parse_float",
+            "String -> Float",
+            fun1(|s| {
+                let s = string(s);
+                s.parse()
+                    .map(Object::Float)
+                    .map_err(|e| anyhow!("Could not parse {} as a Float: {}", fmt_string(&s), e))
+            }),
+        ),
+        // Lists
+        builtin(
+            "map",
+            "(a -> b) -> [a] -> [b]",
+            fun2(|f, xs| {
+                Ok(Object::List(
+                    list(xs)
+                        .into_iter()
+                        .map(|x| apply(&f, x))
+                        .collect::<Result<_>>()?,
+                ))
+            }),
+        ),
+        builtin(
+            "filter",
+            "(a -> Bool) -> [a] -> [a]",
+            fun2(|f, xs| {
+                let mut res = Vec::new();
+                for x in list(xs) {
+                    if let Object::Bool(true) = apply(&f, x.clone())? {
+                        res.push(x);
+                    }
+                }
+                Ok(Object::List(res))
+            }),
+        ),
+        builtin(
+            "fold",
+            "(b -> a -> b) -> b -> [a] -> b",
+            fun3(|f, init, xs| {
+                list(xs)
+                    .into_iter()
+                    .try_fold(init, |acc, x| apply(&apply(&f, acc)?, x))
+            }),
+        ),
+        builtin(
+            "length",
+            "[a] -> Int",
+            fun1(|xs| Ok(Object::Int(list(xs).len() as i64))),
+        ),
+        builtin(
+            "append",
+            "[a] -> [a] -> [a]",
+            fun2(|xs, ys| {
+                let mut xs = list(xs);
+                xs.append(&mut list(ys));
+                Ok(Object::List(xs))
+            }),
+        ),
+    ]
+}
+
+/// `ty` may start with class constraints on its type variables, e.g. `Num a =>
+/// a -> a`
+fn builtin(name: &str, ty: &str, obj: Object) -> Builtin {
+    let (preds, ty) = match ty.split_once("=>") {
+        Some((preds, ty)) => (
+            preds
+                .split(',')
+                .map(
+                    |pred| match pred.split_whitespace().collect::<Vec<_>>()[..] {
+                        ["Eq", var] => (Class::Eq, var.to_string()),
+                        ["Ord", var] => (Class::Ord, var.to_string()),
+                        ["Num", var] => (Class::Num, var.to_string()),
+                        ["Show", var] => (Class::Show, var.to_string()),
+                        _ => panic!("Invalid constraint for builtin {}: {}", name, pred),
+                    },
+                )
+                .collect(),
+            ty.trim(),
+        ),
+        None => (vec![], ty),
+    };
+
+    let ty = parse_ty(
+        Parser::parse(Rule::ty, ty)
+            .unwrap_or_else(|e| panic!("Invalid type for builtin {}: {}", name, e))
+            .next()
+            .unwrap()
+            .into_inner(),
+    )
+    .unwrap();
+
+    let (vars, _, ty) = ty::generalize(&ty::Env::new(), ty);
+
+    (name.to_string(), (vars, preds, ty), obj)
+}
+
+fn fun1(f: impl Fn(Object) -> Result<Object> + 'static) -> Object {
+    Object::Closure(Rc::new(f))
+}
+
+fn fun2(f: impl Fn(Object, Object) -> Result<Object> + 'static) -> Object {
+    let f = Rc::new(f);
+    Object::Closure(Rc::new(move |x| {
+        let f = f.clone();
+        Ok(Object::Closure(Rc::new(move |y| f(x.clone(), y))))
+    }))
+}
+
+fn fun3(f: impl Fn(Object, Object, Object) -> Result<Object> + 'static) -> Object {
+    let f = Rc::new(f);
+    fun2(move |x, y| {
+        let f = f.clone();
+        Ok(Object::Closure(Rc::new(move |z| {
+            f(x.clone(), y.clone(), z)
+        })))
+    })
+}
+
+fn apply(f: &Object, arg: Object) -> Result<Object> {
+    match f {
+        Object::Closure(f) => f(arg),
+        other => unreachable!("{}", other),
+    }
+}
+
+/// An integer operator which fails on overflow and division by zero
+fn arith(f: fn(i64, i64) -> Option<i64>) -> Object {
+    fun2(move |x, y| {
+        let (x, y) = (int(x), int(y));
+        match f(x, y) {
+            Some(i) => Ok(Object::Int(i)),
+            None if y == 0 => Err(anyhow!("Division by zero")),
+            None => Err(anyhow!("Integer overflow")),
+        }
+    })
+}
+
+/// A big integer operator which fails on division by zero
+fn bigint_arith(f: fn(BigInt, BigInt) -> Option<BigInt>) -> Object {
+    fun2(move |x, y| match f(bigint(x), bigint(y)) {
+        Some(i) => Ok(Object::BigInt(i)),
+        None => Err(anyhow!("Division by zero")),
+    })
+}
+
+fn bigint_div(x: BigInt, y: BigInt) -> Option<BigInt> {
+    if y == BigInt::from(0) {
+        None
+    } else {
+        Some(x / y)
+    }
+}
+
+/// An operator on all the `Num` types, which picks the operation for the type
+/// of its first argument. This needs no type information at runtime: the type
+/// checker makes sure that both arguments have the same `Num` type, every
+/// value of such a type carries its own tag, and no `Num` operation is
+/// polymorphic in its result type alone, which would need dispatch on the type
+/// the result is used at instead.
+fn num(
+    int_op: fn(i64, i64) -> Option<i64>,
+    float_op: fn(f64, f64) -> f64,
+    bigint_op: fn(BigInt, BigInt) -> Option<BigInt>,
+    decimal_op: fn(Decimal, Decimal) -> Option<Decimal>,
+) -> Object {
+    let int_op = arith(int_op);
+    let float_op = float_arith(float_op);
+    let bigint_op = bigint_arith(bigint_op);
+    let decimal_op = decimal_arith(decimal_op);
+
+    fun2(move |x, y| {
+        let op = match x {
+            Object::Int(_) => &int_op,
+            Object::Float(_) => &float_op,
+            Object::BigInt(_) => &bigint_op,
+            Object::Decimal(_) => &decimal_op,
+            ref other => unreachable!("{}", other),
+        };
+        apply(&apply(op, x)?, y)
+    })
+}
+
+/// A decimal operator which fails on overflow and division by zero
+fn decimal_arith(f: fn(Decimal, Decimal) -> Option<Decimal>) -> Object {
+    fun2(move |x, y| {
+        let (x, y) = (decimal(x), decimal(y));
+        match f(x, y) {
+            Some(d) => Ok(Object::Decimal(d)),
+            None if y.is_zero() => Err(anyhow!("Division by zero")),
+            None => Err(anyhow!("Decimal overflow")),
+        }
+    })
+}
+
+fn float_arith(f: fn(f64, f64) -> f64) -> Object {
+    fun2(move |x, y| Ok(Object::Float(f(float(x), float(y)))))
+}
+
+/// Convert an integral float to an `Int`, failing if it is out of range
+fn float_to_int(x: f64) -> Result<Object> {
+    // `i64::MAX as f64` rounds up to 2^63, which is itself out of range
+    if x >= i64::MIN as f64 && x < i64::MAX as f64 {
+        Ok(Object::Int(x as i64))
+    } else {
+        Err(anyhow!("{} is out of range for an Int", x))
+    }
+}
+
+/// An equality operator. Unlike the ordering of floats, their equality follows
+/// IEEE 754, so NaN is not equal to anything, not even itself.
+fn equality(f: fn(bool) -> bool) -> Object {
+    fun2(move |x, y| match (&x, &y) {
+        (Object::Closure(_), _) | (_, Object::Closure(_)) => {
+            Err(anyhow!("Cannot compare {} and {}", x, y))
+        }
+        _ => Ok(Object::Bool(f(x == y))),
+    })
+}
+
+fn comparison(f: fn(Ordering) -> bool) -> Object {
+    fun2(move |x, y| Ok(Object::Bool(f(compare(&x, &y)?))))
+}
+
+fn compare(x: &Object, y: &Object) -> Result<Ordering> {
+    x.partial_cmp(y)
+        .ok_or_else(|| anyhow!("Cannot compare {} and {}", x, y))
+}
+
+fn int(obj: Object) -> i64 {
+    match obj {
+        Object::Int(i) => i,
+        other => unreachable!("{}", other),
+    }
+}
+
+fn float(obj: Object) -> f64 {
+    match obj {
+        Object::Float(x) => x,
+        other => unreachable!("{}", other),
+    }
+}
+
+fn bigint(obj: Object) -> BigInt {
+    match obj {
+        Object::BigInt(i) => i,
+        other => unreachable!("{}", other),
+    }
+}
+
+fn decimal(obj: Object) -> Decimal {
+    match obj {
+        Object::Decimal(d) => d,
+        other => unreachable!("{}", other),
+    }
+}
+
+fn bytes(obj: Object) -> Vec<u8> {
+    match obj {
+        Object::Bytes(b) => b,
+        other => unreachable!("{}", other),
+    }
+}
+
+fn date(obj: Object) -> NaiveDate {
+    match obj {
+        Object::Date(d) => d,
+        other => unreachable!("{}", other),
+    }
+}
+
+fn time(obj: Object) -> NaiveTime {
+    match obj {
+        Object::Time(t) => t,
+        other => unreachable!("{}", other),
+    }
+}
+
+fn timestamp(obj: Object) -> DateTime<Utc> {
+    match obj {
+        Object::Timestamp(ts) => ts,
+        other => unreachable!("{}", other),
+    }
+}
+
+fn string(obj: Object) -> String {
+    match obj {
+        Object::String(s) => s,
+        other => unreachable!("{}", other),
+    }
+}
+
+fn list(obj: Object) -> Vec<Object> {
+    match obj {
+        Object::List(objs) => objs,
+        other => unreachable!("{}", other),
+    }
+}
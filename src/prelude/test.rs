@@ -0,0 +1,392 @@
+use super::*;
+use crate::environment::Environment;
+use crate::eval::eval;
+use crate::name_source::NameSource;
+use std::collections::{HashMap, HashSet};
+
+fn infer_and_eval(input: &str) -> (String, String) {
+    let mut ty_env = HashMap::new();
+    let mut env = Environment::new();
+    for (name, scheme, obj) in builtins() {
+        ty_env.insert(name.clone(), scheme);
+        env = env.insert(&name, obj);
+    }
+
+    let e = crate::parse::parse_exprs(
+        Parser::parse(Rule::expr, input)
+            .unwrap_or_else(|e| panic!("{}", e))
+            .next()
+            .unwrap()
+            .into_inner(),
+    )
+    .unwrap();
+
+    let ty = ty::infer(
+        &mut HashMap::new(),
+        &mut NameSource::new(),
+        &ty_env,
+        &HashSet::new(),
+        &e,
+    )
+    .unwrap();
+    let obj = eval(&env, e).unwrap();
+
+    (format!("{}", ty), format!("{}", obj))
+}
+
+#[test]
+fn list_functions() {
+    assert_eq!(
+        ("[Bool]".to_string(), "[false, true]".to_string()),
+        infer_and_eval("map (lambda b -> if b then False else True) [True, False]")
+    );
+
+    assert_eq!(
+        ("[Int]".to_string(), "[1, 3]".to_string()),
+        infer_and_eval("filter (lambda x -> case x of 2 => False | _ => True end) [1, 2, 3]")
+    );
+
+    assert_eq!(
+        ("[Int]".to_string(), "[3, 2, 1]".to_string()),
+        infer_and_eval("fold (lambda acc -> lambda x -> x :: acc) [] [1, 2, 3]")
+    );
+
+    assert_eq!(
+        ("Int".to_string(), "3".to_string()),
+        infer_and_eval("length [(), (), ()]")
+    );
+
+    assert_eq!(
+        ("[String]".to_string(), "[\"a\", \"b\", \"c\"]".to_string()),
+        infer_and_eval("append [\"a\"] [\"b\", \"c\"]")
+    );
+
+    // The builtins are polymorphic
+    assert_eq!(
+        ("(Int, Int)".to_string(), "(2, 1)".to_string()),
+        infer_and_eval("(length [1, 2], length [True])")
+    );
+}
+
+#[test]
+fn arithmetic() {
+    assert_eq!(
+        ("Int".to_string(), "7".to_string()),
+        infer_and_eval("1 + 2 * 3")
+    );
+
+    assert_eq!(
+        ("Int".to_string(), "-1".to_string()),
+        infer_and_eval("(7 - 10) / 2 + 7 % 2 - 1")
+    );
+
+    assert_eq!(
+        ("Int".to_string(), "6".to_string()),
+        infer_and_eval("fold (+) 0 [1, 2, 3]")
+    );
+
+    // The arithmetic operators work on all numeric types
+    assert_eq!(
+        (
+            "(Float, BigInt, Decimal)".to_string(),
+            "(3.75, 6n, 0.30d)".to_string()
+        ),
+        infer_and_eval("(1.5 + 2.25, 2n * 3n, 0.10d + 0.20d)")
+    );
+
+    assert_eq!(
+        (
+            "(Float, BigInt, Decimal)".to_string(),
+            "(3.5, 3n, 2.50d)".to_string()
+        ),
+        infer_and_eval("(7.0 / 2.0, 7n / 2n, 5.00d / 2d)")
+    );
+
+    // Functions using them are polymorphic in the numeric type
+    assert_eq!(
+        ("(Int, Float)".to_string(), "(4, 3.0)".to_string()),
+        infer_and_eval("let double = lambda x -> x + x in (double 2, double 1.5) end")
+    );
+}
+
+#[test]
+fn comparison() {
+    assert_eq!(
+        (
+            "[Bool]".to_string(),
+            "[true, false, true, false]".to_string()
+        ),
+        infer_and_eval("[1 < 2, (1, \"b\") <= (1, \"a\"), [1, 2] == 1 :: [2], not True]")
+    );
+
+    assert_eq!(
+        ("[Int]".to_string(), "[-1, 0, 1]".to_string()),
+        infer_and_eval("[compare 1 2, compare \"a\" \"a\", compare { x = True } { x = False }]")
+    );
+}
+
+#[test]
+fn strings() {
+    assert_eq!(
+        ("String".to_string(), "\"HELLO, WORLD\"".to_string()),
+        infer_and_eval("to_upper (concat \"hello, \" \"world\")")
+    );
+
+    assert_eq!(
+        ("(Int, String)".to_string(), "(5, \"æøå\")".to_string()),
+        infer_and_eval("(string_length \"hæøåx\", substring \"hæøåx\" 1 3)")
+    );
+
+    assert_eq!(
+        ("Int".to_string(), "-42".to_string()),
+        infer_and_eval("parse_int (int_to_string (0 - 42))")
+    );
+
+    assert_eq!(
+        (
+            "[String]".to_string(),
+            "[\"[1, 2]\", \"\\\"a\\\"\", \"(1.5, ())\"]".to_string()
+        ),
+        infer_and_eval("[show [1, 2], show \"a\", show (1.5, ())]")
+    );
+}
+
+#[test]
+fn runtime_errors() {
+    fn eval_err(input: &str) -> String {
+        let e = crate::parse::parse_exprs(
+            Parser::parse(Rule::expr, input)
+                .unwrap_or_else(|e| panic!("{}", e))
+                .next()
+                .unwrap()
+                .into_inner(),
+        )
+        .unwrap();
+
+        let env = builtins()
+            .into_iter()
+            .fold(Environment::new(), |env, (name, _, obj)| {
+                env.insert(&name, obj)
+            });
+
+        format!("{}", eval(&env, e).err().unwrap())
+    }
+
+    assert_eq!("Division by zero", eval_err("1 / 0"));
+
+    assert_eq!("Integer overflow", eval_err("9223372036854775807 + 1"));
+
+    assert!(eval_err("truncate 1e19").contains("out of range"));
+
+    assert_eq!("Integer overflow", eval_err("0 - 9223372036854775807 - 2"));
+
+    assert!(eval_err("bigint_to_int 9223372036854775808n").contains("out of range"));
+
+    assert_eq!("Division by zero", eval_err("1n / 0n"));
+
+    assert!(eval_err("slice_bytes hex\"00\" 1 1").contains("out of bounds"));
+
+    assert!(eval_err("bytes_to_string hex\"ff\"").contains("not valid UTF-8"));
+
+    assert!(eval_err("add_days 9223372036854775807 @2026-10-17").contains("out of range"));
+
+    assert_eq!("Division by zero", eval_err("1d / 0d"));
+
+    assert_eq!(
+        "Decimal overflow",
+        eval_err("79228162514264337593543950335d * 2d")
+    );
+
+    assert!(eval_err("round (0.0 /. 0.0)").contains("out of range"));
+
+    assert!(eval_err("parse_int \"foo\"").starts_with("Could not parse"));
+
+    assert!(eval_err("substring \"foo\" 2 2").contains("out of bounds"));
+
+    assert!(eval_err("(lambda x -> x) == (lambda x -> x)").starts_with("Cannot compare"));
+}
+
+#[test]
+fn floats() {
+    assert_eq!(
+        ("Float".to_string(), "5.5".to_string()),
+        infer_and_eval("1.5 +. 2.0 *. 3.0 -. 4.0 /. 2.0")
+    );
+
+    assert_eq!(
+        ("(Float, Int, Int)".to_string(), "(2.0, 2, -3)".to_string()),
+        infer_and_eval("(sqrt (int_to_float 4), truncate 2.7, round (0.0 -. 2.5))")
+    );
+
+    assert_eq!(
+        ("[Bool]".to_string(), "[true, false]".to_string()),
+        infer_and_eval("[1.5 < 2.0, 0.1 +. 0.2 == 0.3]")
+    );
+
+    // NaN is unequal to everything, but ordered after every other float
+    assert_eq!(
+        (
+            "[Bool]".to_string(),
+            "[false, true, false, true, false]".to_string()
+        ),
+        infer_and_eval(
+            "let nan = 0.0 /. 0.0 in \
+             [nan == nan, nan != nan, nan < nan, 1.0 < nan, nan <= 1e308] end"
+        )
+    );
+
+    assert_eq!(
+        ("[Int]".to_string(), "[0, 1, -1]".to_string()),
+        infer_and_eval(
+            "let nan = 0.0 /. 0.0 in \
+             [compare nan nan, compare nan (0.0 -. 1e308), compare 1.0 nan] end"
+        )
+    );
+
+    assert_eq!(
+        ("Float".to_string(), "0.25".to_string()),
+        infer_and_eval("parse_float (float_to_string 0.25)")
+    );
+}
+
+#[test]
+fn bigints_and_decimals() {
+    assert_eq!(
+        (
+            "BigInt".to_string(),
+            "85070591730234615884290395931651604481n".to_string()
+        ),
+        infer_and_eval("let x = int_to_bigint 9223372036854775807 + 2n in x * x end")
+    );
+
+    assert_eq!(
+        (
+            "(Decimal, Decimal)".to_string(),
+            "(0.30d, 3.33d)".to_string()
+        ),
+        infer_and_eval("(0.10d + 0.20d, round_decimal 2 (10d / 3d))")
+    );
+
+    assert_eq!(
+        ("[Int]".to_string(), "[42, -2, 3]".to_string()),
+        infer_and_eval(
+            "[bigint_to_int (43n - 1n), decimal_to_int (-2.9d), \
+             decimal_to_int (bigint_to_decimal (7n / 2n))]"
+        )
+    );
+
+    assert_eq!(
+        ("(Decimal, Float)".to_string(), "(0.5d, 0.25)".to_string()),
+        infer_and_eval("(float_to_decimal 0.5, decimal_to_float (1d / 4d))")
+    );
+
+    assert_eq!(
+        ("Bool".to_string(), "true".to_string()),
+        infer_and_eval("1.50d == 1.5d")
+    );
+}
+
+#[test]
+fn dates_and_times() {
+    assert_eq!(
+        ("(Date, Int)".to_string(), "(@2027-01-01, -365)".to_string()),
+        infer_and_eval("(add_days 76 @2026-10-17, days_between @2027-01-01 @2026-01-01)")
+    );
+
+    assert_eq!(
+        ("Timestamp".to_string(), "@2026-10-18T00:00:30Z".to_string()),
+        infer_and_eval("add_seconds 60 @2026-10-17T23:59:30Z")
+    );
+
+    assert_eq!(
+        ("Int".to_string(), "5400".to_string()),
+        infer_and_eval("seconds_between @2026-10-17T12:00:00Z @2026-10-17T15:00:00+01:30")
+    );
+
+    assert_eq!(
+        ("[Int]".to_string(), "[2026, 10, 17]".to_string()),
+        infer_and_eval("let d = @2026-10-17 in [year d, month d, day d] end")
+    );
+
+    assert_eq!(
+        (
+            "(Date, Time, Timestamp)".to_string(),
+            "(@2026-10-17, @12:30:00, @2026-01-01T09:00:00Z)".to_string()
+        ),
+        infer_and_eval(
+            "let ts = @2026-10-17T12:30:00Z in \
+             (date_of ts, time_of ts, to_timestamp @2026-01-01 @09:00) end"
+        )
+    );
+
+    assert_eq!(
+        ("[Bool]".to_string(), "[true, true, true]".to_string()),
+        infer_and_eval(
+            "[@2026-10-17 < @2026-10-18, @23:59 > @00:00, \
+             @2026-10-17T12:00:00Z == @2026-10-17T14:00:00+02:00]"
+        )
+    );
+
+    assert_eq!(
+        ("Bool".to_string(), "true".to_string()),
+        infer_and_eval("now () > @2020-01-01T00:00:00Z")
+    );
+}
+
+#[test]
+fn bytes() {
+    assert_eq!(
+        ("(Int, Bytes)".to_string(), "(4, hex\"adbe\")".to_string()),
+        infer_and_eval("let b = hex\"deadbeef\" in (bytes_length b, slice_bytes b 1 2) end")
+    );
+
+    assert_eq!(
+        (
+            "(String, String)".to_string(),
+            "(\"00ff10\", \"AP8Q\")".to_string()
+        ),
+        infer_and_eval("let b = append_bytes hex\"00ff\" hex\"10\" in (to_hex b, to_base64 b) end")
+    );
+
+    assert_eq!(
+        (
+            "(Bytes, String)".to_string(),
+            "(hex\"c3a6\", \"æ\")".to_string()
+        ),
+        infer_and_eval("(string_to_bytes \"æ\", bytes_to_string hex\"c3a6\")")
+    );
+}
+
+#[test]
+fn unicode_strings() {
+    // "e" followed by a combining acute accent is a single grapheme
+    assert_eq!(
+        ("(Int, String)".to_string(), "(3, \"e\u{301}\")".to_string()),
+        infer_and_eval("(string_length \"ae\\u0301b\", substring \"ae\\u0301b\" 1 1)")
+    );
+
+    assert_eq!(
+        ("[Bool]".to_string(), "[false, true, true]".to_string()),
+        infer_and_eval(
+            "[\"\\u00e9\" == \"e\\u0301\", \
+             normalize_nfc \"e\\u0301\" == \"\\u00e9\", \
+             normalize_nfd \"\\u00e9\" == \"e\\u0301\"]"
+        )
+    );
+
+    assert_eq!(
+        (
+            "(String, String)".to_string(),
+            "(\"x2\", \"fi\")".to_string()
+        ),
+        infer_and_eval("(normalize_nfkc \"x\\u00b2\", normalize_nfkd \"\\ufb01\")")
+    );
+
+    assert_eq!(
+        (
+            "[String]".to_string(),
+            "[\"strasse\", \"strasse\", \"straße\"]".to_string()
+        ),
+        infer_and_eval("[case_fold \"Straße\", case_fold \"STRASSE\", to_lower \"STRAßE\"]")
+    );
+}
@@ -1,7 +1,11 @@
+#![allow(clippy::result_large_err)]
+
 use crate::ast::*;
 use crate::name_source::*;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::fmt;
 use std::iter;
 
 #[cfg(test)]
@@ -10,70 +14,355 @@ mod test;
 /// Type substitutions
 type Substitution = (Ident, Ty);
 
+/// The type variables that have been unified with another type, mapped to that
+/// type
 type GlobalSub = HashMap<Ident, Ty>;
 
 type Constraint = (Ty, Ty);
 
-/// Type scheme
-type Scheme = (Vec<Ident>, Ty);
+/// The fields of a record type, and its row variable if it is extensible
+type Row = (Vec<(Ident, Ty)>, Option<Ident>);
+
+/// The maximum nesting of expressions that can be type checked. Inference
+/// recurses over the expression, so deeper expressions would overflow the
+/// stack.
+pub const MAX_DEPTH: usize = 10_000;
+
+/// The built-in type classes
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub enum Class {
+    /// Types whose values can be compared for equality, i.e. those without
+    /// functions
+    Eq,
+    /// Types whose values can be ordered, i.e. those without functions
+    Ord,
+    /// The numeric types `Int`, `Float`, `BigInt` and `Decimal`
+    Num,
+    /// Types whose values can be shown as a string, i.e. those without functions
+    Show,
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// A class constraint on a type variable, e.g. `Num a`
+pub type Predicate = (Class, Ident);
+
+/// Type scheme: the quantified variables, the constraints on them and the type
+pub type Scheme = (Vec<Ident>, Vec<Predicate>, Ty);
 
 /// Type Environment
 pub type Env = HashMap<Ident, Scheme>;
 
-pub fn unify(
-    mut constraints: impl Iterator<Item = Constraint>,
-) -> Box<dyn Iterator<Item = Result<Substitution, String>>> {
-    match constraints.next() {
-        None => Box::new(iter::empty()),
-        Some((t1, t2)) if t1 == t2 => unify(constraints),
-        Some((Ty::Var(ident), other)) if other.fv().all(|x| x != ident) => {
-            let subst = (ident, other);
-            let tmp: Vec<_> = constraints.map(|x| x.apply(&subst)).collect();
-            Box::new(unify(tmp.into_iter()).chain(iter::once(Ok(subst))))
-        }
-        Some((other, Ty::Var(ident))) if other.fv().all(|x| x != ident) => {
-            let subst = (ident, other);
-            let tmp: Vec<_> = constraints.map(|x| x.apply(&subst)).collect();
-            Box::new(unify(tmp.into_iter()).chain(iter::once(Ok(subst))))
-        }
-        Some((Ty::Fun(lhs1, rhs1), Ty::Fun(lhs2, rhs2))) => {
-            let tmp: Vec<_> = iter::once((*lhs1, *lhs2))
-                .chain(iter::once((*rhs1, *rhs2)))
-                .chain(constraints)
-                .collect();
-            Box::new(unify(tmp.into_iter()))
-        }
-        Some((Ty::Tuple(tys1), Ty::Tuple(tys2))) if tys1.len() == tys2.len() => {
-            let tmp: Vec<_> = tys1.into_iter().zip(tys2).chain(constraints).collect();
-            Box::new(unify(tmp.into_iter()))
-        }
-        Some((Ty::Record(mut tys1), Ty::Record(mut tys2))) => {
-            tys1.sort_by_key(|(k, _)| k.clone());
-            tys2.sort_by_key(|(k, _)| k.clone());
-
-            if tys1.len() == tys2.len()
-                && tys1.iter().map(|(k, _)| k).eq(tys2.iter().map(|(k, _)| k))
-            {
-                let tmp: Vec<_> = tys1
-                    .into_iter()
-                    .map(|(_, x)| x)
-                    .zip(tys2.into_iter().map(|(_, x)| x))
-                    .chain(constraints)
-                    .collect();
-                Box::new(unify(tmp.into_iter()))
-            } else {
-                Box::new(iter::once(Err(format!(
-                    "Could not unify records {} and {}",
-                    Ty::Record(tys1),
-                    Ty::Record(tys2)
-                ))))
+/// A class constraint that remains to be solved, along with the identifier
+/// whose use gave rise to it
+type Wanted = (Class, Ty, Vec<(Expr, Ty)>);
+
+/// Declared types, mapping each type name to its type parameters
+pub type TypeDefs = HashMap<Ident, Vec<Ident>>;
+
+/// Type aliases, mapping each alias to its type parameters and the type it
+/// stands for
+pub type Aliases = HashMap<Ident, (Vec<Ident>, Ty)>;
+
+/// Type errors. The types in an error are resolved as far as they were known
+/// when the error occurred.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TypeError {
+    /// Two types that should be equal are not. `sources` are the
+    /// subexpressions whose types were being unified, with their types.
+    Mismatch {
+        expected: Ty,
+        actual: Ty,
+        sources: Vec<(Expr, Ty)>,
+    },
+    /// Unifying the variable with the type would make an infinite type
+    Occurs {
+        var: Ident,
+        ty: Ty,
+        sources: Vec<(Expr, Ty)>,
+    },
+    /// Two record types have different fields
+    Fields {
+        expected: Ty,
+        actual: Ty,
+        missing: Vec<Ident>,
+        extra: Vec<Ident>,
+        sources: Vec<(Expr, Ty)>,
+    },
+    /// An identifier or constructor is not in the environment, along with the
+    /// names in the environment that are similar to it
+    Unbound {
+        ident: Ident,
+        suggestions: Vec<Ident>,
+    },
+    /// A constructor pattern has the wrong number of arguments
+    Arity {
+        constructor: Ident,
+        expected: usize,
+        actual: usize,
+        pat: Pattern,
+    },
+    Refutable(Pattern),
+    /// The alternatives of an or-pattern bind different variables
+    OrBinders(Pattern),
+    RecNotLambda(Ident, Box<Expr>),
+    /// A type variable of an annotation is used both as a type and as the row
+    /// variable of a record type
+    RowAsType(Ident),
+    /// A type is used with a function that requires it to be an instance of a
+    /// class it is not an instance of
+    NoInstance {
+        class: Class,
+        ty: Ty,
+        sources: Vec<(Expr, Ty)>,
+    },
+    /// An expression is nested more than `MAX_DEPTH` expressions deep
+    TooDeep,
+}
+
+impl TypeError {
+    /// Swap the expected and actual types, for unifications where the type
+    /// that is known first is the actual one
+    fn flip(self) -> Self {
+        match self {
+            TypeError::Mismatch {
+                expected,
+                actual,
+                sources,
+            } => TypeError::Mismatch {
+                expected: actual,
+                actual: expected,
+                sources,
+            },
+            TypeError::Fields {
+                expected,
+                actual,
+                missing,
+                extra,
+                sources,
+            } => TypeError::Fields {
+                expected: actual,
+                actual: expected,
+                missing: extra,
+                extra: missing,
+                sources,
+            },
+            err => err,
+        }
+    }
+
+    /// The types shown in the error message, in the order they are shown
+    fn types(&self) -> Vec<Ty> {
+        let (tys, sources) = match self {
+            TypeError::Mismatch {
+                expected,
+                actual,
+                sources,
+            }
+            | TypeError::Fields {
+                expected,
+                actual,
+                sources,
+                ..
+            } => (vec![expected.clone(), actual.clone()], sources),
+            TypeError::Occurs { var, ty, sources } => {
+                (vec![Ty::Var(var.clone()), ty.clone()], sources)
+            }
+            TypeError::NoInstance { ty, sources, .. } => (vec![ty.clone()], sources),
+            _ => return vec![],
+        };
+
+        tys.into_iter()
+            .chain(sources.iter().map(|(_, ty)| ty.clone()))
+            .collect()
+    }
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Show the type variables with readable names rather than the ones
+        // made by the `NameSource`
+        let mut old = Vec::new();
+        for ty in self.types() {
+            for var in ty.fv() {
+                if !old.contains(&var) {
+                    old.push(var);
+                }
             }
         }
-        Some((t1, t2)) => Box::new(iter::once(Err(format!(
-            "Could not unify {} and {}",
-            t1, t2
-        )))),
+        let new: Vec<_> = var_names().take(old.len()).map(Ty::Var).collect();
+        let show = |ty: &Ty| substitute_all(ty, &old, new.clone());
+
+        let fmt_sources = |f: &mut fmt::Formatter<'_>, sources: &[(Expr, Ty)]| {
+            for (expr, ty) in sources {
+                write!(f, "\n  `{}` has type {}", expr, show(ty))?;
+            }
+            Ok(())
+        };
+
+        match self {
+            TypeError::Mismatch {
+                expected,
+                actual,
+                sources,
+            } => {
+                write!(
+                    f,
+                    "Type mismatch: expected {}, but found {}",
+                    show(expected),
+                    show(actual)
+                )?;
+                fmt_sources(f, sources)
+            }
+            TypeError::Occurs { var, ty, sources } => {
+                write!(
+                    f,
+                    "Infinite type: {} cannot be equal to {}, which contains it",
+                    show(&Ty::Var(var.clone())),
+                    show(ty)
+                )?;
+                fmt_sources(f, sources)
+            }
+            TypeError::Fields {
+                expected,
+                actual,
+                missing,
+                extra,
+                sources,
+            } => {
+                write!(
+                    f,
+                    "Record fields do not match: expected {}, but found {}",
+                    show(expected),
+                    show(actual)
+                )?;
+                if !missing.is_empty() {
+                    write!(f, "\n  missing fields: {}", missing.join(", "))?;
+                }
+                if !extra.is_empty() {
+                    write!(f, "\n  extra fields: {}", extra.join(", "))?;
+                }
+                fmt_sources(f, sources)
+            }
+            TypeError::Unbound { ident, suggestions } => {
+                write!(f, "Unbound identifier {}", ident)?;
+                if !suggestions.is_empty() {
+                    write!(f, "\n  did you mean {}?", suggestions.join(" or "))?;
+                }
+                Ok(())
+            }
+            TypeError::Arity {
+                constructor,
+                expected,
+                actual,
+                pat,
+            } => write!(
+                f,
+                "Constructor {} takes {} arguments, but is given {} in pattern {}",
+                constructor, expected, actual, pat
+            ),
+            TypeError::Refutable(pat) => write!(
+                f,
+                "The pattern {} is refutable, so it cannot be used in a let binding or as a parameter",
+                pat
+            ),
+            TypeError::OrBinders(pat) => write!(
+                f,
+                "The alternatives of pattern {} must bind the same variables",
+                pat
+            ),
+            TypeError::RecNotLambda(ident, e) => write!(
+                f,
+                "Recursive binding {} must be a lambda, but got {}",
+                ident, e
+            ),
+            TypeError::RowAsType(var) => write!(
+                f,
+                "Type variable {} is used both as a type and as the rest of a record",
+                var
+            ),
+            TypeError::NoInstance { class, ty, sources } => {
+                write!(f, "Type {} is not an instance of {}", show(ty), class)?;
+                fmt_sources(f, sources)
+            }
+            TypeError::TooDeep => write!(
+                f,
+                "The expression is nested more than {} expressions deep",
+                MAX_DEPTH
+            ),
+        }
+    }
+}
+
+/// The names in `env` that are at most a few edits away from `ident`, closest
+/// first. Operators are never suggested, and neither is anything for names so
+/// short that every other short name would be.
+fn suggestions(env: &Env, ident: &str) -> Vec<Ident> {
+    let len = ident.chars().count();
+    let max = (len / 3).clamp(1, 2).min(len.saturating_sub(1));
+    let mut res: Vec<_> = env
+        .keys()
+        .filter(|name| name.starts_with(|c: char| c.is_ascii_alphabetic()))
+        .map(|name| (edit_distance(ident, name), name))
+        .filter(|(dist, _)| *dist <= max)
+        .collect();
+    res.sort();
+
+    res.into_iter()
+        .take(3)
+        .map(|(_, name)| name.clone())
+        .collect()
+}
+
+/// The Levenshtein distance between `a` and `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, x) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, y) in b.iter().enumerate() {
+            let subst = prev[j] + if x == *y { 0 } else { 1 };
+            cur.push(subst.min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
     }
+
+    prev[b.len()]
+}
+
+/// Unify the pairs of types in `constraints`, returning the substitutions that
+/// make them equal, the last one found first.
+pub fn unify(
+    constraints: impl Iterator<Item = Constraint>,
+) -> Box<dyn Iterator<Item = Result<Substitution, TypeError>>> {
+    let mut global_sub = GlobalSub::new();
+    let mut name_src = NameSource::new();
+    let newtypes = HashSet::new();
+    let mut inference = Inference::new(&mut global_sub, &mut name_src, &newtypes);
+
+    for (t1, t2) in constraints {
+        if let Err(e) = inference.unify(t1, t2) {
+            return Box::new(iter::once(Err(e)));
+        }
+    }
+
+    let substs: Vec<_> = inference
+        .bound
+        .clone()
+        .into_iter()
+        .rev()
+        .map(|ident| {
+            let ty = inference.resolve(&Ty::Var(ident.clone()));
+            Ok((ident, ty))
+        })
+        .collect();
+
+    Box::new(substs.into_iter())
 }
 
 /// `infer` is based on Algorithm J
@@ -83,179 +372,1516 @@ pub fn infer(
     global_sub: &mut GlobalSub,
     name_src: &mut NameSource,
     env: &Env,
+    newtypes: &HashSet<Ident>,
     expr: &Expr,
-) -> Result<Ty, String> {
-    match expr {
-        Expr::Atom(Atom::Int(_)) => Ok(Ty::Int),
-        Expr::Atom(Atom::Bool(_)) => Ok(Ty::Bool),
-        Expr::Atom(Atom::String(_)) => Ok(Ty::String),
-        Expr::Ident(ident) => {
-            let scheme = env
-                .get(ident)
-                .ok_or_else(|| format!("Identifier {} not found in environment", ident))?;
-            Ok(instantiate(scheme, name_src))
-        }
-        Expr::Let(binds, expr) => {
-            let mut env = env.clone();
-            for (ident, e) in binds {
-                let ty = infer(global_sub, name_src, &env, e)?;
+) -> Result<Ty, TypeError> {
+    let mut inference = Inference::new(global_sub, name_src, newtypes);
+    let ty = inference.infer(env, expr)?;
+    inference.solve(env)?;
+
+    Ok(inference.resolve(&ty))
+}
+
+/// Infer the generalized type scheme of `expr`, along with the class
+/// constraints on its type variables
+pub fn infer_scheme(
+    global_sub: &mut GlobalSub,
+    name_src: &mut NameSource,
+    env: &Env,
+    newtypes: &HashSet<Ident>,
+    expr: &Expr,
+) -> Result<Scheme, TypeError> {
+    let mut inference = Inference::new(global_sub, name_src, newtypes);
+    inference.level += 1;
+    let ty = inference.infer(env, expr)?;
+    inference.level -= 1;
+    inference.solve(env)?;
+
+    Ok(inference.generalize(&ty))
+}
+
+/// Infer the type of binding the irrefutable pattern `pat` to `e`, and return
+/// the generalized type scheme of each variable bound by the pattern.
+pub fn infer_let(
+    global_sub: &mut GlobalSub,
+    name_src: &mut NameSource,
+    env: &Env,
+    newtypes: &HashSet<Ident>,
+    pat: &Pattern,
+    e: &Expr,
+) -> Result<Vec<(Ident, Scheme)>, TypeError> {
+    Inference::new(global_sub, name_src, newtypes).infer_let(env, pat, e)
+}
+
+/// Infer the type schemes of a group of mutually recursive bindings. The
+/// bindings are monomorphic within the group, and only generalized afterwards.
+pub fn infer_rec(
+    global_sub: &mut GlobalSub,
+    name_src: &mut NameSource,
+    env: &Env,
+    newtypes: &HashSet<Ident>,
+    binds: &[(Ident, Expr)],
+) -> Result<Vec<(Ident, Scheme)>, TypeError> {
+    Inference::new(global_sub, name_src, newtypes).infer_rec(env, binds)
+}
+
+/// Type variables partitioned into classes of variables that have been unified
+/// with each other. Each variable is numbered, and each class is a tree of
+/// numbers whose root stands for the whole class: its name, level and the type
+/// it has been bound to, if any, are only kept up to date for the root.
+#[derive(Default)]
+struct UnionFind {
+    index: HashMap<Ident, usize>,
+    parent: Vec<usize>,
+    size: Vec<usize>,
+    name: Vec<Ident>,
+    level: Vec<usize>,
+    ty: Vec<Option<Ty>>,
+}
+
+impl UnionFind {
+    /// The number of `ident`, adding it as a class of its own at level 0 if it
+    /// has not been seen before
+    fn index(&mut self, ident: &str) -> usize {
+        if let Some(i) = self.index.get(ident) {
+            return *i;
+        }
+        self.insert(ident.to_string(), 0)
+    }
+
+    fn insert(&mut self, ident: Ident, level: usize) -> usize {
+        let i = self.parent.len();
+        self.index.insert(ident.clone(), i);
+        self.parent.push(i);
+        self.size.push(1);
+        self.name.push(ident);
+        self.level.push(level);
+        self.ty.push(None);
+        i
+    }
 
-                let scheme = generalize(&env, ty);
+    /// The root of the class of `i`. The variables along the way are updated
+    /// to point directly to the root.
+    fn root(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+
+        let mut i = i;
+        while self.parent[i] != root {
+            let next = self.parent[i];
+            self.parent[i] = root;
+            i = next;
+        }
 
-                env.insert(ident.clone(), scheme);
+        root
+    }
+
+    fn find(&mut self, ident: &str) -> usize {
+        let i = self.index(ident);
+        self.root(i)
+    }
+
+    /// Merge the class of `a` into that of `b`, keeping the name of `b`. The
+    /// smaller tree is attached to the root of the larger one.
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.root(a), self.root(b));
+        if a == b {
+            return;
+        }
+
+        let name = self.name[b].clone();
+        let level = self.level[a].min(self.level[b]);
+        let ty = self.ty[b].take().or_else(|| self.ty[a].take());
+        let (child, root) = if self.size[a] > self.size[b] {
+            (b, a)
+        } else {
+            (a, b)
+        };
+
+        self.parent[child] = root;
+        self.size[root] += self.size[child];
+        self.name[root] = name;
+        self.level[root] = level;
+        self.ty[root] = ty;
+    }
+}
+
+/// The state of type inference. Type variables form a union-find structure in
+/// `vars`, where unifying a variable with a type binds its class to that type.
+/// The bindings of `global_sub` are read into it when inference starts, and
+/// written back when it is done.
+///
+/// Each unbound variable has a level, the number of enclosing let bindings
+/// when it was created. A let binding generalizes the variables of a higher
+/// level than its own, since they cannot be reachable from the environment.
+/// Variables without a level, such as those of the environment we started
+/// from, are at level 0.
+struct Inference<'a> {
+    global_sub: &'a mut GlobalSub,
+    name_src: &'a mut NameSource,
+    /// The constructors of newtypes, whose patterns are irrefutable
+    newtypes: &'a HashSet<Ident>,
+    vars: UnionFind,
+    level: usize,
+    /// The variables in the order they were bound
+    bound: Vec<Ident>,
+    /// The class constraints that have not been solved yet. Once solved, they
+    /// only constrain unbound variables.
+    wanted: Vec<Wanted>,
+    /// The number of expressions being inferred that enclose the current one
+    depth: usize,
+}
+
+impl<'a> Inference<'a> {
+    fn new(
+        global_sub: &'a mut GlobalSub,
+        name_src: &'a mut NameSource,
+        newtypes: &'a HashSet<Ident>,
+    ) -> Self {
+        let mut vars = UnionFind::default();
+        for (ident, ty) in global_sub.iter() {
+            let i = vars.index(ident);
+            match ty {
+                Ty::Var(var) => {
+                    let j = vars.index(var);
+                    vars.union(i, j);
+                }
+                ty => {
+                    let root = vars.root(i);
+                    vars.ty[root] = Some(ty.clone());
+                }
             }
+        }
 
-            infer(global_sub, name_src, &env, expr)
+        Inference {
+            global_sub,
+            name_src,
+            newtypes,
+            vars,
+            level: 0,
+            bound: Vec::new(),
+            wanted: Vec::new(),
+            depth: 0,
         }
-        Expr::Lambda(ident, e) => {
-            let freshvar = name_src.fresh(&ident);
+    }
+
+    fn fresh(&mut self, name: &str) -> Ty {
+        Ty::Var(self.fresh_var(name))
+    }
+
+    fn fresh_var(&mut self, name: &str) -> Ident {
+        let ident = self.name_src.fresh(name);
+        self.vars.insert(ident.clone(), self.level);
+        ident
+    }
+
+    fn level_of(&mut self, ident: &str) -> usize {
+        let root = self.vars.find(ident);
+        self.vars.level[root]
+    }
+
+    /// Infer the type of `expr`, failing if it is nested more than
+    /// `MAX_DEPTH` expressions deep. The cases that recurse are kept in
+    /// separate methods, so that each level of nesting only takes up the stack
+    /// needed for its own kind of expression.
+    fn infer(&mut self, env: &Env, expr: &Expr) -> Result<Ty, TypeError> {
+        if self.depth == MAX_DEPTH {
+            return Err(TypeError::TooDeep);
+        }
+
+        self.depth += 1;
+        let res = self.infer_expr(env, expr);
+        self.depth -= 1;
+
+        res
+    }
+
+    fn infer_expr(&mut self, env: &Env, expr: &Expr) -> Result<Ty, TypeError> {
+        match expr {
+            Expr::Atom(Atom::Int(_)) => Ok(Ty::Int),
+            Expr::Atom(Atom::Float(_)) => Ok(Ty::Float),
+            Expr::Atom(Atom::BigInt(_)) => Ok(Ty::BigInt),
+            Expr::Atom(Atom::Decimal(_)) => Ok(Ty::Decimal),
+            Expr::Atom(Atom::Date(_)) => Ok(Ty::Date),
+            Expr::Atom(Atom::Time(_)) => Ok(Ty::Time),
+            Expr::Atom(Atom::Timestamp(_)) => Ok(Ty::Timestamp),
+            Expr::Atom(Atom::Bytes(_)) => Ok(Ty::Bytes),
+            Expr::Atom(Atom::Bool(_)) => Ok(Ty::Bool),
+            Expr::Atom(Atom::String(_)) => Ok(Ty::String),
+            Expr::Atom(Atom::Unit) => Ok(Ty::Unit),
+            Expr::Ident(ident) => self.infer_ident(env, expr, ident),
+            Expr::Let(binds, body) => self.infer_let_in(env, binds, body),
+            Expr::LetRec(binds, body) => self.infer_rec_in(env, binds, body),
+            Expr::Lambda(ident, ty, body) => self.infer_lambda(env, ident, ty, body),
+            Expr::Annot(e, ty) => self.infer_annot(env, e, ty),
+            Expr::Apply(e1, e2) => self.infer_apply(env, e1, e2),
+            Expr::Tuple(exprs) => self.infer_tuple(env, exprs),
+            Expr::List(exprs) => self.infer_list(env, exprs),
+            Expr::Cons(head, tail) => self.infer_cons(env, head, tail),
+            Expr::Record(recs) => self.infer_record(env, recs),
+            Expr::Case(e, matches) => self.infer_case(env, e, matches),
+            Expr::If(cond, then, els) => self.infer_if(env, cond, then, els),
+            Expr::Field(e, field) => self.infer_field(env, e, field),
+            Expr::Update(e, updates) => self.infer_update(env, e, updates),
+        }
+    }
+
+    fn infer_ident(&mut self, env: &Env, expr: &Expr, ident: &str) -> Result<Ty, TypeError> {
+        let scheme = env.get(ident).ok_or_else(|| TypeError::Unbound {
+            ident: ident.to_string(),
+            suggestions: suggestions(env, ident),
+        })?;
+
+        // The constraints of the scheme are blamed on this use of it
+        let start = self.wanted.len();
+        let ty = self.instantiate(scheme);
+        for wanted in &mut self.wanted[start..] {
+            wanted.2 = vec![(expr.clone(), ty.clone())];
+        }
+
+        Ok(ty)
+    }
+
+    fn infer_let_in(
+        &mut self,
+        env: &Env,
+        binds: &[(Pattern, Expr)],
+        body: &Expr,
+    ) -> Result<Ty, TypeError> {
+        let mut env = env.clone();
+        for (pat, e) in binds {
+            for (ident, scheme) in self.infer_let(&env, pat, e)? {
+                env.insert(ident, scheme);
+            }
+        }
+
+        self.infer(&env, body)
+    }
+
+    fn infer_rec_in(
+        &mut self,
+        env: &Env,
+        binds: &[(Ident, Expr)],
+        body: &Expr,
+    ) -> Result<Ty, TypeError> {
+        let mut env = env.clone();
+        for (ident, scheme) in self.infer_rec(&env, binds)? {
+            env.insert(ident, scheme);
+        }
+
+        self.infer(&env, body)
+    }
+
+    fn infer_lambda(
+        &mut self,
+        env: &Env,
+        ident: &str,
+        ty: &Option<Ty>,
+        body: &Expr,
+    ) -> Result<Ty, TypeError> {
+        let param_ty = match ty {
+            Some(ty) => self.instantiate_annotation(ty)?,
+            None => self.fresh(ident),
+        };
+        let mut env = env.clone();
+        env.insert(ident.to_string(), (vec![], vec![], param_ty.clone()));
+        let rhs = self.infer(&env, body)?;
+
+        Ok(Ty::Fun(Box::new(param_ty), Box::new(rhs)))
+    }
+
+    fn infer_annot(&mut self, env: &Env, e: &Expr, ty: &Ty) -> Result<Ty, TypeError> {
+        let ty = self.instantiate_annotation(ty)?;
+        self.check(env, e, &ty)?;
+
+        Ok(ty)
+    }
+
+    fn infer_apply(&mut self, env: &Env, e1: &Expr, e2: &Expr) -> Result<Ty, TypeError> {
+        let t1 = self.infer(env, e1)?;
+        let t2 = self.infer(env, e2)?;
+        let fresh = self.fresh("arg");
+
+        self.unify(
+            t1.clone(),
+            Ty::Fun(Box::new(t2.clone()), Box::new(fresh.clone())),
+        )
+        .map_err(|err| self.blame(err, &[(e1, &t1), (e2, &t2)]))?;
+
+        Ok(fresh)
+    }
+
+    fn infer_tuple(&mut self, env: &Env, exprs: &[Expr]) -> Result<Ty, TypeError> {
+        let mut res = Vec::new();
+
+        for expr in exprs {
+            res.push(self.infer(env, expr)?);
+        }
+
+        Ok(Ty::Tuple(res))
+    }
+
+    fn infer_list(&mut self, env: &Env, exprs: &[Expr]) -> Result<Ty, TypeError> {
+        let elem = self.fresh("elem");
+
+        for expr in exprs {
+            let ty = self.infer(env, expr)?;
+            self.unify(elem.clone(), ty.clone())
+                .map_err(|err| self.blame(err, &[(expr, &ty)]))?;
+        }
+
+        Ok(Ty::List(Box::new(elem)))
+    }
+
+    fn infer_cons(&mut self, env: &Env, head: &Expr, tail: &Expr) -> Result<Ty, TypeError> {
+        let head_ty = self.infer(env, head)?;
+        let tail_ty = self.infer(env, tail)?;
+        let list_ty = Ty::List(Box::new(head_ty.clone()));
+        self.unify(list_ty.clone(), tail_ty.clone())
+            .map_err(|err| self.blame(err, &[(head, &head_ty), (tail, &tail_ty)]))?;
+
+        Ok(list_ty)
+    }
+
+    fn infer_record(&mut self, env: &Env, recs: &[(Ident, Expr)]) -> Result<Ty, TypeError> {
+        let mut res = Vec::new();
+        for (ident, expr) in recs {
+            res.push((ident.clone(), self.infer(env, expr)?));
+        }
+
+        Ok(Ty::Record(res, None))
+    }
+
+    fn infer_case(
+        &mut self,
+        env: &Env,
+        expr: &Expr,
+        matches: &[(Pattern, Option<Expr>, Expr)],
+    ) -> Result<Ty, TypeError> {
+        // Find the type of expr
+        let ty = self.infer(env, expr)?;
+
+        // Bind the result to a fresh type variable
+        let fresh = self.fresh("case");
+
+        for (pat, guard, e) in matches {
+            // verify that pat unifies with ty
+            let vars = self.bind_pat(env, expr, &ty, pat)?;
+
+            // Bind the variables of the pattern in the local
+            // environment. Like lambda-bound variables, they are not
+            // generalized.
             let mut env = env.clone();
-            env.insert(ident.clone(), (vec![], Ty::Var(freshvar.clone())));
-            let rhs = infer(global_sub, name_src, &env, e)?;
+            for (ident, var) in vars {
+                env.insert(ident, (vec![], vec![], var));
+            }
+
+            if let Some(guard) = guard {
+                let guard_ty = self.infer(&env, guard)?;
+                self.unify(Ty::Bool, guard_ty.clone())
+                    .map_err(|err| self.blame(err, &[(guard, &guard_ty)]))?;
+            }
+
+            // the type of e
+            let e_ty = self.infer(&env, e)?;
+
+            // Verify e_ty unifies with the result type, which is
+            // expected to be that of the previous arms
+            self.unify(e_ty.clone(), fresh.clone())
+                .map_err(|err| self.blame(err.flip(), &[(e, &e_ty)]))?;
+        }
+
+        Ok(fresh)
+    }
+
+    fn infer_if(
+        &mut self,
+        env: &Env,
+        cond: &Expr,
+        then: &Expr,
+        els: &Expr,
+    ) -> Result<Ty, TypeError> {
+        let cond_ty = self.infer(env, cond)?;
+        self.unify(Ty::Bool, cond_ty.clone())
+            .map_err(|err| self.blame(err, &[(cond, &cond_ty)]))?;
 
-            let lhs = global_sub
-                .get(&freshvar)
-                .cloned()
-                .unwrap_or(Ty::Var(freshvar));
+        let then_ty = self.infer(env, then)?;
+        let els_ty = self.infer(env, els)?;
+        self.unify(then_ty.clone(), els_ty.clone())
+            .map_err(|err| self.blame(err, &[(then, &then_ty), (els, &els_ty)]))?;
 
-            Ok(Ty::Fun(Box::new(lhs), Box::new(rhs)))
+        Ok(then_ty)
+    }
+
+    fn infer_field(&mut self, env: &Env, e: &Expr, field: &str) -> Result<Ty, TypeError> {
+        let ty = self.infer(env, e)?;
+
+        self.field_ty(e, &ty, field)
+    }
+
+    fn infer_update(
+        &mut self,
+        env: &Env,
+        e: &Expr,
+        updates: &[(Ident, Expr)],
+    ) -> Result<Ty, TypeError> {
+        let ty = self.infer(env, e)?;
+
+        for (field, e_inner) in updates {
+            let field_ty = self.field_ty(e, &ty, field)?;
+
+            let e_ty = self.infer(env, e_inner)?;
+            self.unify(field_ty, e_ty.clone())
+                .map_err(|err| self.blame(err, &[(e_inner, &e_ty)]))?;
         }
-        Expr::Apply(e1, e2) => {
-            let t1 = infer(global_sub, name_src, env, e1)?;
-            let t2 = infer(global_sub, name_src, env, e2)?;
-            let fresh = name_src.fresh(&"arg");
-            let substs = unify(iter::once((
-                t1,
-                Ty::Fun(Box::new(t2), Box::new(Ty::Var(fresh.clone()))),
-            )))
-            .collect::<Result<Vec<_>, String>>()?;
 
-            // Apply substs in global substitution
-            for (ident, ty) in substs {
-                global_sub.insert(ident, ty);
+        Ok(ty)
+    }
+
+    /// Check that `e` has the type `ty`. Unannotated lambdas take their
+    /// parameter types from `ty`, which lets annotations guide the inference
+    /// of, for example, record field accesses on the parameters.
+    fn check(&mut self, env: &Env, e: &Expr, ty: &Ty) -> Result<(), TypeError> {
+        match (e, self.find(ty.clone())) {
+            (Expr::Lambda(ident, None, body), Ty::Fun(param_ty, res_ty)) => {
+                let mut env = env.clone();
+                env.insert(ident.clone(), (vec![], vec![], *param_ty));
+                self.check(&env, body, &res_ty)
+            }
+            _ => {
+                let e_ty = self.infer(env, e)?;
+                self.unify(ty.clone(), e_ty.clone())
+                    .map_err(|err| self.blame(err, &[(e, &e_ty)]))
             }
+        }
+    }
+
+    /// Give the type variables of an annotation fresh names, so that they are
+    /// only shared within the annotation itself.
+    fn instantiate_annotation(&mut self, ty: &Ty) -> Result<Ty, TypeError> {
+        let mut rows = HashSet::new();
+        let mut types = HashSet::new();
+        var_kinds(ty, &mut rows, &mut types);
+        if let Some(var) = rows.intersection(&types).next() {
+            return Err(TypeError::RowAsType(var.clone()));
+        }
+
+        let mut vars: Vec<Ident> = Vec::new();
+        for var in ty.fv() {
+            if !vars.contains(&var) {
+                vars.push(var);
+            }
+        }
 
-            let lhs = global_sub.get(&fresh).cloned().unwrap_or(Ty::Var(fresh));
+        Ok(self.instantiate(&(vars, vec![], ty.clone())))
+    }
+
+    fn infer_let(
+        &mut self,
+        env: &Env,
+        pat: &Pattern,
+        e: &Expr,
+    ) -> Result<Vec<(Ident, Scheme)>, TypeError> {
+        self.level += 1;
+        let ty = self.infer(env, e)?;
+
+        if let Pattern::Ident(ident) = pat {
+            self.level -= 1;
+            self.solve(env)?;
+            let scheme = self.generalize(&ty);
+            self.forget_generalized();
+            return Ok(vec![(ident.clone(), scheme)]);
+        }
 
-            Ok(lhs)
+        if !irrefutable(self.newtypes, pat) {
+            return Err(TypeError::Refutable(pat.clone()));
         }
-        Expr::Tuple(exprs) => {
-            let mut res = Vec::new();
 
-            for expr in exprs {
-                res.push(infer(global_sub, name_src, env, expr)?);
+        let vars = self.bind_pat(env, e, &ty, pat)?;
+        self.level -= 1;
+        self.solve(env)?;
+
+        let schemes = binders(pat)
+            .into_iter()
+            .map(|ident| {
+                let scheme = self.generalize(&vars[&ident]);
+                (ident, scheme)
+            })
+            .collect();
+        self.forget_generalized();
+
+        Ok(schemes)
+    }
+
+    fn infer_rec(
+        &mut self,
+        env: &Env,
+        binds: &[(Ident, Expr)],
+    ) -> Result<Vec<(Ident, Scheme)>, TypeError> {
+        let mut rec_env = env.clone();
+        let mut vars = Vec::new();
+
+        self.level += 1;
+        for (ident, e) in binds {
+            if !matches!(e, Expr::Lambda(..)) {
+                return Err(TypeError::RecNotLambda(ident.clone(), Box::new(e.clone())));
             }
 
-            Ok(Ty::Tuple(res))
+            let fresh = self.fresh(ident);
+            rec_env.insert(ident.clone(), (vec![], vec![], fresh.clone()));
+            vars.push(fresh);
+        }
+
+        for ((_, e), var) in binds.iter().zip(vars.iter()) {
+            let ty = self.infer(&rec_env, e)?;
+            self.unify(var.clone(), ty.clone())
+                .map_err(|err| self.blame(err, &[(e, &ty)]))?;
         }
-        Expr::Record(recs) => {
-            let mut res = Vec::new();
-            for (ident, expr) in recs {
-                res.push((ident.clone(), infer(global_sub, name_src, env, expr)?));
+        self.level -= 1;
+        self.solve(env)?;
+
+        let schemes = binds
+            .iter()
+            .zip(vars.iter())
+            .map(|((ident, _), var)| (ident.clone(), self.generalize(var)))
+            .collect();
+        self.forget_generalized();
+
+        Ok(schemes)
+    }
+
+    /// Unify `pat` with the type `ty` of the matched value `e`, returning the
+    /// types of the pattern variables.
+    fn bind_pat(
+        &mut self,
+        env: &Env,
+        e: &Expr,
+        ty: &Ty,
+        pat: &Pattern,
+    ) -> Result<HashMap<Ident, Ty>, TypeError> {
+        // Each pattern variable gets a fresh type variable, so it cannot clash
+        // with other type variables of the same name
+        let mut vars = HashMap::new();
+        for ident in binders(pat) {
+            if let Entry::Vacant(entry) = vars.entry(ident) {
+                let var = self.fresh(entry.key());
+                entry.insert(var);
             }
+        }
 
-            Ok(Ty::Record(res))
+        for (t1, t2) in self.unify_pat(env, ty, pat, &vars)? {
+            self.unify(t1, t2)
+                .map_err(|err| self.blame(err, &[(e, ty)]))?;
         }
-        Expr::Atom(Atom::Unit) => Ok(Ty::Unit),
-        Expr::Case(expr, matches) => {
-            // Find the type of expr
-            let ty = infer(global_sub, name_src, env, expr)?;
 
-            // Bind the result to a fresh type variable
-            let fresh = name_src.fresh("case");
+        Ok(vars)
+    }
 
-            for (pat, e) in matches {
-                // verify that pat unifies with ty
-                let pat_substs = unify(unify_pat(name_src, &ty, pat).into_iter())
-                    .collect::<Result<Vec<_>, String>>()?;
+    /// Find the type of `field` in the type `ty` of `e`, which must be a
+    /// record with at least that field
+    fn field_ty(&mut self, e: &Expr, ty: &Ty, field: &str) -> Result<Ty, TypeError> {
+        let field_ty = self.fresh("field");
+        let row = self.fresh_var("row");
 
-                let mut env = env.clone();
-                for (ident, ty) in pat_substs {
-                    // Insert the result both in global_sub and in the local
-                    // environment? Is it necessary to add it to global_sub?
-                    global_sub.insert(ident.clone(), ty.clone());
+        self.unify(
+            Ty::Record(vec![(field.to_string(), field_ty.clone())], Some(row)),
+            ty.clone(),
+        )
+        .map_err(|err| self.blame(err, &[(e, ty)]))?;
 
-                    let scheme = generalize(&env, ty);
+        Ok(field_ty)
+    }
+
+    /// Attach the subexpressions whose types were being unified to a
+    /// unification error, unless an error from a nested unification already
+    /// has them.
+    fn blame(&mut self, err: TypeError, exprs: &[(&Expr, &Ty)]) -> TypeError {
+        let mut resolved = Vec::new();
+        for (e, ty) in exprs {
+            resolved.push(((*e).clone(), self.resolve(ty)));
+        }
 
-                    env.insert(ident.to_string(), scheme);
+        match err {
+            TypeError::Mismatch {
+                expected,
+                actual,
+                sources,
+            } if sources.is_empty() => TypeError::Mismatch {
+                expected,
+                actual,
+                sources: resolved,
+            },
+            TypeError::Occurs { var, ty, sources } if sources.is_empty() => TypeError::Occurs {
+                var,
+                ty,
+                sources: resolved,
+            },
+            TypeError::Fields {
+                expected,
+                actual,
+                missing,
+                extra,
+                sources,
+            } if sources.is_empty() => TypeError::Fields {
+                expected,
+                actual,
+                missing,
+                extra,
+                sources: resolved,
+            },
+            err => err,
+        }
+    }
+
+    /// The type that the variable `ty` has been bound to, or the variable
+    /// naming its class if it is unbound. Types that are not variables are
+    /// returned as they are.
+    fn find(&mut self, ty: Ty) -> Ty {
+        match ty {
+            Ty::Var(ident) => {
+                let root = self.vars.find(&ident);
+                match &self.vars.ty[root] {
+                    Some(ty) => ty.clone(),
+                    None => Ty::Var(self.vars.name[root].clone()),
                 }
+            }
+            ty => ty,
+        }
+    }
 
-                let result_ty = global_sub
-                    .get(&fresh)
-                    .cloned()
-                    .unwrap_or_else(|| Ty::Var(fresh.clone()));
+    /// Replace all bound type variables in `ty` by their types
+    fn resolve(&mut self, ty: &Ty) -> Ty {
+        match ty {
+            Ty::Var(_) => match self.find(ty.clone()) {
+                var @ Ty::Var(_) => var,
+                bound => self.resolve(&bound),
+            },
+            Ty::Tuple(tys) => Ty::Tuple(tys.iter().map(|x| self.resolve(x)).collect()),
+            Ty::Record(recs, row) => match self.flatten(recs.clone(), row.clone()) {
+                Ok((recs, row)) => Ty::Record(self.resolve_fields(&recs), row),
+                Err(_) => Ty::Record(self.resolve_fields(recs), row.clone()),
+            },
+            Ty::Fun(lhs, rhs) => Ty::Fun(Box::new(self.resolve(lhs)), Box::new(self.resolve(rhs))),
+            Ty::Defined(name, args) => {
+                Ty::Defined(name.clone(), args.iter().map(|x| self.resolve(x)).collect())
+            }
+            Ty::List(ty) => Ty::List(Box::new(self.resolve(ty))),
+            _ => ty.clone(),
+        }
+    }
 
-                // the type of e
-                let e_ty = infer(global_sub, name_src, &env, e)?;
+    fn resolve_fields(&mut self, recs: &[(Ident, Ty)]) -> Vec<(Ident, Ty)> {
+        recs.iter()
+            .map(|(ident, ty)| (ident.clone(), self.resolve(ty)))
+            .collect()
+    }
 
-                // Verify e_ty unifies with result_ty and insert substs?
-                let e_substs =
-                    unify(iter::once((e_ty, result_ty))).collect::<Result<Vec<_>, String>>()?;
+    /// Collect the fields of a record type whose row variable has been bound
+    /// to further fields, returning all the fields and the unbound row
+    /// variable, if any. Fails if a row variable has been used as a type.
+    fn flatten(
+        &mut self,
+        mut recs: Vec<(Ident, Ty)>,
+        mut row: Option<Ident>,
+    ) -> Result<Row, TypeError> {
+        while let Some(var) = row.take() {
+            match self.find(Ty::Var(var.clone())) {
+                Ty::Var(var) => {
+                    row = Some(var);
+                    break;
+                }
+                Ty::Record(more, rest) => {
+                    recs.extend(more);
+                    row = rest;
+                }
+                other => {
+                    return Err(TypeError::Mismatch {
+                        expected: Ty::Record(vec![], Some(var)),
+                        actual: self.resolve(&other),
+                        sources: Vec::new(),
+                    })
+                }
+            }
+        }
+
+        Ok((recs, row))
+    }
+    /// Unify `t1` and `t2`, binding type variables as needed. The pairs of
+    /// types left to unify are kept on a stack, so deeply nested types do not
+    /// lead to deep recursion.
+    fn unify(&mut self, t1: Ty, t2: Ty) -> Result<(), TypeError> {
+        let mut stack = vec![(t1, t2)];
+
+        while let Some((t1, t2)) = stack.pop() {
+            match (self.find(t1), self.find(t2)) {
+                (t1, t2) if t1 == t2 => (),
+                (Ty::Var(ident), other) | (other, Ty::Var(ident)) => self.bind(ident, other)?,
+                (Ty::Fun(lhs1, rhs1), Ty::Fun(lhs2, rhs2)) => {
+                    stack.push((*rhs1, *rhs2));
+                    stack.push((*lhs1, *lhs2));
+                }
+                (Ty::Tuple(tys1), Ty::Tuple(tys2)) if tys1.len() == tys2.len() => {
+                    stack.extend(tys1.into_iter().zip(tys2).rev());
+                }
+                (Ty::List(ty1), Ty::List(ty2)) => stack.push((*ty1, *ty2)),
+                (Ty::Defined(name1, args1), Ty::Defined(name2, args2))
+                    if name1 == name2 && args1.len() == args2.len() =>
+                {
+                    stack.extend(args1.into_iter().zip(args2).rev());
+                }
+                (Ty::Record(tys1, row1), Ty::Record(tys2, row2)) => {
+                    let (mut tys1, row1) = self.flatten(tys1, row1)?;
+                    let (tys2, row2) = self.flatten(tys2, row2)?;
+                    tys1.sort_by_key(|(k, _)| k.clone());
+
+                    // The fields only in one of the records have to be
+                    // covered by the row variable of the other
+                    let mut only1 = Vec::new();
+                    let mut common = Vec::new();
+                    for (k, ty1) in &tys1 {
+                        match tys2.iter().find(|(k2, _)| k == k2) {
+                            Some((_, ty2)) => common.push((ty1.clone(), ty2.clone())),
+                            None => only1.push((k.clone(), ty1.clone())),
+                        }
+                    }
+                    let only2: Vec<_> = tys2
+                        .iter()
+                        .filter(|(k, _)| tys1.iter().all(|(k1, _)| k != k1))
+                        .cloned()
+                        .collect();
+
+                    match (&row1, &row2) {
+                        (None, None) if only1.is_empty() && only2.is_empty() => (),
+                        (Some(r1), None) if only1.is_empty() => {
+                            self.bind(r1.clone(), Ty::Record(only2, None))?
+                        }
+                        (None, Some(r2)) if only2.is_empty() => {
+                            self.bind(r2.clone(), Ty::Record(only1, None))?
+                        }
+                        (Some(r1), Some(r2)) if r1 != r2 => {
+                            if only1.is_empty() {
+                                self.bind(r1.clone(), Ty::Record(only2, Some(r2.clone())))?
+                            } else if only2.is_empty() {
+                                self.bind(r2.clone(), Ty::Record(only1, Some(r1.clone())))?
+                            } else {
+                                let rest = self.fresh_var("row");
+                                self.bind(r1.clone(), Ty::Record(only2, Some(rest.clone())))?;
+                                self.bind(r2.clone(), Ty::Record(only1, Some(rest)))?;
+                            }
+                        }
+                        (Some(_), Some(_)) if only1.is_empty() && only2.is_empty() => (),
+                        _ => {
+                            let missing = match row2 {
+                                Some(_) if row1 != row2 => Vec::new(),
+                                _ => only1.iter().map(|(k, _)| k.clone()).collect(),
+                            };
+                            let extra = match row1 {
+                                Some(_) if row1 != row2 => Vec::new(),
+                                _ => only2.iter().map(|(k, _)| k.clone()).collect(),
+                            };
+
+                            return Err(TypeError::Fields {
+                                expected: self.resolve(&Ty::Record(tys1, row1)),
+                                actual: self.resolve(&Ty::Record(tys2, row2)),
+                                missing,
+                                extra,
+                                sources: Vec::new(),
+                            });
+                        }
+                    }
+
+                    stack.extend(common.into_iter().rev());
+                }
+                (t1, t2) => {
+                    return Err(TypeError::Mismatch {
+                        expected: self.resolve(&t1),
+                        actual: self.resolve(&t2),
+                        sources: Vec::new(),
+                    })
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Bind the unbound variable `ident` to `ty`, unless `ident` occurs in `ty`
+    fn bind(&mut self, ident: Ident, ty: Ty) -> Result<(), TypeError> {
+        let level = self.level_of(&ident);
+        if self.occurs(&ident, level, &ty) {
+            return Err(TypeError::Occurs {
+                ty: self.resolve(&ty),
+                var: ident,
+                sources: Vec::new(),
+            });
+        }
+
+        let i = self.vars.find(&ident);
+        match ty {
+            Ty::Var(var) => {
+                let j = self.vars.index(&var);
+                self.vars.union(i, j);
+            }
+            ty => self.vars.ty[i] = Some(ty),
+        }
+        self.bound.push(ident);
+
+        Ok(())
+    }
+
+    /// Whether the variable `ident` occurs in `ty`. The unbound variables of
+    /// `ty` become reachable from `ident`, so their levels are lowered to at
+    /// most `level`.
+    fn occurs(&mut self, ident: &str, level: usize, ty: &Ty) -> bool {
+        match ty {
+            Ty::Var(_) => match self.find(ty.clone()) {
+                Ty::Var(var) => {
+                    let root = self.vars.find(&var);
+                    if self.vars.level[root] > level {
+                        self.vars.level[root] = level;
+                    }
+                    var == ident
+                }
+                bound => self.occurs(ident, level, &bound),
+            },
+            Ty::Tuple(tys) | Ty::Defined(_, tys) => {
+                tys.iter().any(|ty| self.occurs(ident, level, ty))
+            }
+            Ty::Record(recs, row) => {
+                recs.iter().any(|(_, ty)| self.occurs(ident, level, ty))
+                    || row
+                        .iter()
+                        .any(|row| self.occurs(ident, level, &Ty::Var(row.clone())))
+            }
+            Ty::Fun(lhs, rhs) => self.occurs(ident, level, lhs) || self.occurs(ident, level, rhs),
+            Ty::List(ty) => self.occurs(ident, level, ty),
+            _ => false,
+        }
+    }
+
+    /// Give the quantified variables of `scheme` fresh names. Its constraints
+    /// on them become wanted constraints on the fresh variables.
+    fn instantiate(&mut self, scheme: &Scheme) -> Ty {
+        let (vars, preds, mut res) = scheme.clone();
+        let mut fresh_vars = HashMap::new();
+        for ident in vars {
+            let fresh = self.fresh(&ident);
+            res = res.apply(&(ident.clone(), fresh.clone()));
+            fresh_vars.insert(ident, fresh);
+        }
+
+        for (class, var) in preds {
+            let ty = fresh_vars.get(&var).cloned().unwrap_or(Ty::Var(var));
+            self.wanted.push((class, ty, Vec::new()));
+        }
+
+        res
+    }
+
+    /// Generalize the unbound variables of `ty` that were created at a higher
+    /// level than the current one, along with the solved constraints on them
+    fn generalize(&mut self, ty: &Ty) -> Scheme {
+        let ty = self.resolve(ty);
+        let mut vars = Vec::new();
+        for var in ty.fv() {
+            if self.level_of(&var) > self.level && !vars.contains(&var) {
+                vars.push(var);
+            }
+        }
+
+        let mut preds = Vec::new();
+        for (class, ty, _) in &self.wanted {
+            if let Ty::Var(var) = ty {
+                if vars.contains(var) && !preds.contains(&(*class, var.clone())) {
+                    preds.push((*class, var.clone()));
+                }
+            }
+        }
+
+        (vars, preds, ty)
+    }
+
+    /// Drop the solved constraints on variables that have just been
+    /// generalized, or that are not reachable from the environment at all
+    fn forget_generalized(&mut self) {
+        let wanted = std::mem::take(&mut self.wanted);
+        self.wanted = wanted
+            .into_iter()
+            .filter(|(_, ty, _)| match ty {
+                Ty::Var(var) => self.level_of(var) <= self.level,
+                _ => true,
+            })
+            .collect();
+    }
+
+    /// Reduce the wanted constraints to constraints on unbound variables,
+    /// failing if a constraint is on a type that is not an instance of the
+    /// class
+    fn solve(&mut self, env: &Env) -> Result<(), TypeError> {
+        let wanted = std::mem::take(&mut self.wanted);
+
+        for (class, ty, sources) in wanted {
+            let mut vars = Vec::new();
+            if let Err(ty) = self.reduce(env, class, &ty, &mut HashSet::new(), &mut vars) {
+                let ty = self.resolve(&ty);
+                let sources = sources
+                    .iter()
+                    .map(|(e, ty)| (e.clone(), self.resolve(ty)))
+                    .collect();
+                return Err(TypeError::NoInstance { class, ty, sources });
+            }
+
+            for var in vars {
+                let var = Ty::Var(var);
+                if !self
+                    .wanted
+                    .iter()
+                    .any(|(c, ty, _)| *c == class && ty == &var)
+                {
+                    self.wanted.push((class, var, sources.clone()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reduce the constraint that `ty` is an instance of `class` to
+    /// constraints on the unbound variables of `ty`, which are added to
+    /// `vars`. On failure, returns the part of `ty` that is not an instance.
+    ///
+    /// A declared type is an instance if the arguments of its constructors
+    /// are. `seen` holds the declared types that are already being checked, so
+    /// recursive types are only unfolded once.
+    fn reduce(
+        &mut self,
+        env: &Env,
+        class: Class,
+        ty: &Ty,
+        seen: &mut HashSet<Ident>,
+        vars: &mut Vec<Ident>,
+    ) -> Result<(), Ty> {
+        match self.find(ty.clone()) {
+            Ty::Var(var) => {
+                if !vars.contains(&var) {
+                    vars.push(var);
+                }
+                Ok(())
+            }
+            Ty::Int | Ty::Float | Ty::BigInt | Ty::Decimal => Ok(()),
+            ty if class == Class::Num => Err(ty),
+            ty @ Ty::Fun(..) => Err(ty),
+            Ty::Tuple(tys) => tys
+                .iter()
+                .try_for_each(|ty| self.reduce(env, class, ty, seen, vars)),
+            Ty::List(ty) => self.reduce(env, class, &ty, seen, vars),
+            Ty::Record(recs, row) => {
+                let (recs, row) = self
+                    .flatten(recs.clone(), row.clone())
+                    .map_err(|_| Ty::Record(recs, row))?;
+                for (_, ty) in &recs {
+                    self.reduce(env, class, ty, seen, vars)?;
+                }
+                if let Some(row) = row {
+                    self.reduce(env, class, &Ty::Var(row), seen, vars)?;
+                }
+                Ok(())
+            }
+            Ty::Defined(name, args) => {
+                if !seen.insert(name.clone()) {
+                    return args
+                        .iter()
+                        .try_for_each(|ty| self.reduce(env, class, ty, seen, vars));
+                }
+
+                // The constructors are the capitalized names in the
+                // environment whose types result in this type
+                let mut constructors: Vec<_> = env
+                    .iter()
+                    .filter(|(con, _)| con.starts_with(|c: char| c.is_ascii_uppercase()))
+                    .collect();
+                constructors.sort_by_key(|(con, _)| *con);
+
+                for (_, scheme) in constructors {
+                    let mut con_ty = self.instantiate(scheme);
+                    let mut con_args = Vec::new();
+                    while let Ty::Fun(arg, res) = con_ty {
+                        con_args.push(*arg);
+                        con_ty = *res;
+                    }
+
+                    match con_ty {
+                        Ty::Defined(con_name, params) if con_name == name => {
+                            for (param, arg) in params.into_iter().zip(args.iter()) {
+                                self.unify(param, arg.clone())
+                                    .expect("constructor parameters are distinct");
+                            }
+                            for arg in con_args {
+                                self.reduce(env, class, &arg, seen, vars)?;
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn unify_pat(
+        &mut self,
+        env: &Env,
+        ty: &Ty,
+        pat: &Pattern,
+        vars: &HashMap<Ident, Ty>,
+    ) -> Result<Vec<Constraint>, TypeError> {
+        match pat {
+            Pattern::Atom(Atom::Unit) => Ok(vec![(ty.clone(), Ty::Unit)]),
+            Pattern::Atom(Atom::Bool(_)) => Ok(vec![(ty.clone(), Ty::Bool)]),
+            Pattern::Atom(Atom::Int(_)) => Ok(vec![(ty.clone(), Ty::Int)]),
+            Pattern::Atom(Atom::Float(_)) => Ok(vec![(ty.clone(), Ty::Float)]),
+            Pattern::Atom(Atom::BigInt(_)) => Ok(vec![(ty.clone(), Ty::BigInt)]),
+            Pattern::Atom(Atom::Decimal(_)) => Ok(vec![(ty.clone(), Ty::Decimal)]),
+            Pattern::Atom(Atom::Date(_)) => Ok(vec![(ty.clone(), Ty::Date)]),
+            Pattern::Atom(Atom::Time(_)) => Ok(vec![(ty.clone(), Ty::Time)]),
+            Pattern::Atom(Atom::Timestamp(_)) => Ok(vec![(ty.clone(), Ty::Timestamp)]),
+            Pattern::Atom(Atom::Bytes(_)) => Ok(vec![(ty.clone(), Ty::Bytes)]),
+            Pattern::Atom(Atom::String(_)) => Ok(vec![(ty.clone(), Ty::String)]),
+            Pattern::Ident(ident) => Ok(vec![(ty.clone(), vars[ident].clone())]),
+            Pattern::Tuple(pats) => {
+                let mut constraints = Vec::new();
+                let mut freshvars = Vec::new();
+
+                for pat in pats {
+                    // make fresh variables and add that to the unify chain
+                    let result_ty = self.fresh("case");
+                    freshvars.push(result_ty.clone());
+                    constraints.append(&mut self.unify_pat(env, &result_ty, pat, vars)?);
+                }
+                constraints.push((ty.clone(), Ty::Tuple(freshvars)));
+
+                Ok(constraints)
+            }
+            Pattern::Record(recs, is_open) => {
+                let freshvars: Vec<_> = recs
+                    .iter()
+                    .map(|(ident, _)| (ident.clone(), self.fresh("case")))
+                    .collect();
+
+                // An open record pattern matches records with other fields
+                // as well
+                let row = if *is_open {
+                    Some(self.fresh_var("row"))
+                } else {
+                    None
+                };
+                let mut constraints = vec![(ty.clone(), Ty::Record(freshvars.clone(), row))];
+
+                for ((_, pat), (_, result_ty)) in recs.iter().zip(freshvars.iter()) {
+                    constraints.append(&mut self.unify_pat(env, result_ty, pat, vars)?);
+                }
+
+                Ok(constraints)
+            }
+            Pattern::Wildcard => Ok(vec![]),
+            Pattern::List(pats) => {
+                let elem = self.fresh("elem");
+                let mut constraints = Vec::new();
 
-                // Insert substs in global_sub?
-                for (ident, ty) in e_substs {
-                    global_sub.insert(ident, ty);
+                for pat in pats {
+                    constraints.append(&mut self.unify_pat(env, &elem, pat, vars)?);
                 }
+                constraints.push((ty.clone(), Ty::List(Box::new(elem))));
+
+                Ok(constraints)
+            }
+            Pattern::Cons(head, tail) => {
+                let elem = self.fresh("elem");
+                let list_ty = Ty::List(Box::new(elem.clone()));
+
+                let mut constraints = self.unify_pat(env, &elem, head, vars)?;
+                constraints.append(&mut self.unify_pat(env, &list_ty, tail, vars)?);
+                constraints.push((ty.clone(), list_ty));
+
+                Ok(constraints)
             }
+            Pattern::Constructor(name, pats) => {
+                let scheme = env.get(name).ok_or_else(|| TypeError::Unbound {
+                    ident: name.clone(),
+                    suggestions: suggestions(env, name),
+                })?;
 
-            let res = global_sub.get(&fresh).cloned().unwrap_or(Ty::Var(fresh));
+                let mut con_ty = self.instantiate(scheme);
+
+                let mut arity = 0;
+                let mut res_ty = &con_ty;
+                while let Ty::Fun(_, res) = res_ty {
+                    arity += 1;
+                    res_ty = res;
+                }
+
+                if arity != pats.len() {
+                    return Err(TypeError::Arity {
+                        constructor: name.clone(),
+                        expected: arity,
+                        actual: pats.len(),
+                        pat: pat.clone(),
+                    });
+                }
 
-            Ok(res)
+                // Peel off one argument type of the constructor for each pattern
+                let mut constraints = Vec::new();
+                for pat in pats {
+                    match con_ty {
+                        Ty::Fun(arg, res) => {
+                            constraints.append(&mut self.unify_pat(env, &arg, pat, vars)?);
+                            con_ty = *res;
+                        }
+                        other => unreachable!("{}", other),
+                    }
+                }
+
+                constraints.push((ty.clone(), con_ty));
+
+                Ok(constraints)
+            }
+            Pattern::Or(pats) => {
+                let mut expected = binders(&pats[0]);
+                expected.sort();
+
+                let mut constraints = Vec::new();
+                for pat in pats {
+                    let mut bound = binders(pat);
+                    bound.sort();
+                    if bound != expected {
+                        return Err(TypeError::OrBinders(Pattern::Or(pats.clone())));
+                    }
+
+                    // The alternatives share the type variables of the
+                    // pattern variables, so they bind them at the same types.
+                    constraints.append(&mut self.unify_pat(env, ty, pat, vars)?);
+                }
+
+                Ok(constraints)
+            }
+            Pattern::As(pat, ident) => {
+                let mut constraints = self.unify_pat(env, ty, pat, vars)?;
+                constraints.push((ty.clone(), vars[ident].clone()));
+
+                Ok(constraints)
+            }
         }
     }
 }
 
-fn unify_pat(name_src: &mut NameSource, ty: &Ty, pat: &Pattern) -> Vec<Constraint> {
+/// Whether `pat` matches every value of its type. Apart from the constructors
+/// of the newtypes in `newtypes`, constructor patterns are considered
+/// refutable, even for types with a single constructor.
+fn irrefutable(newtypes: &HashSet<Ident>, pat: &Pattern) -> bool {
+    let all = |pats: &[Pattern]| pats.iter().all(|pat| irrefutable(newtypes, pat));
+
+    match pat {
+        Pattern::Ident(_) | Pattern::Wildcard => true,
+        Pattern::Tuple(pats) => all(pats),
+        Pattern::Record(recs, _) => recs.iter().all(|(_, pat)| irrefutable(newtypes, pat)),
+        Pattern::Or(pats) => pats.iter().any(|pat| irrefutable(newtypes, pat)),
+        Pattern::As(pat, _) => irrefutable(newtypes, pat),
+        Pattern::Atom(Atom::Unit) => true,
+        Pattern::Constructor(name, pats) if newtypes.contains(name) => all(pats),
+        Pattern::Atom(_) | Pattern::Constructor(..) | Pattern::List(_) | Pattern::Cons(..) => false,
+    }
+}
+
+/// The variables bound by a pattern
+fn binders(pat: &Pattern) -> Vec<Ident> {
     match pat {
-        Pattern::Atom(Atom::Unit) => vec![(ty.clone(), Ty::Unit)],
-        Pattern::Atom(Atom::Bool(_)) => vec![(ty.clone(), Ty::Bool)],
-        Pattern::Atom(Atom::Int(_)) => vec![(ty.clone(), Ty::Int)],
-        Pattern::Atom(Atom::String(_)) => vec![(ty.clone(), Ty::String)],
-        Pattern::Ident(ident) => vec![(ty.clone(), Ty::Var(ident.clone()))],
-        Pattern::Tuple(pats) => {
-            let mut constraints = Vec::new();
-            let mut freshvars = Vec::new();
+        Pattern::Atom(_) | Pattern::Wildcard => vec![],
+        Pattern::Ident(ident) => vec![ident.clone()],
+        Pattern::Tuple(pats) | Pattern::Constructor(_, pats) | Pattern::List(pats) => {
+            pats.iter().flat_map(binders).collect()
+        }
+        Pattern::Cons(head, tail) => binders(head).into_iter().chain(binders(tail)).collect(),
+        Pattern::Record(recs, _) => recs.iter().flat_map(|(_, pat)| binders(pat)).collect(),
+        // The alternatives all bind the same variables
+        Pattern::Or(pats) => binders(&pats[0]),
+        Pattern::As(pat, ident) => {
+            let mut idents = binders(pat);
+            idents.push(ident.clone());
+            idents
+        }
+    }
+}
+
+/// Collect the variables used as row variables of records in `ty` in `rows`,
+/// and the other type variables in `types`
+fn var_kinds(ty: &Ty, rows: &mut HashSet<Ident>, types: &mut HashSet<Ident>) {
+    match ty {
+        Ty::Var(ident) => {
+            types.insert(ident.clone());
+        }
+        Ty::Record(recs, row) => {
+            rows.extend(row.iter().cloned());
+            for (_, ty) in recs {
+                var_kinds(ty, rows, types);
+            }
+        }
+        Ty::Tuple(tys) | Ty::Defined(_, tys) => {
+            for ty in tys {
+                var_kinds(ty, rows, types);
+            }
+        }
+        Ty::Fun(lhs, rhs) => {
+            var_kinds(lhs, rows, types);
+            var_kinds(rhs, rows, types);
+        }
+        Ty::List(ty) => var_kinds(ty, rows, types),
+        _ => (),
+    }
+}
 
-            for pat in pats {
-                // make fresh variables and add that to the unify chain
-                let fresh = name_src.fresh("case");
-                let result_ty = Ty::Var(fresh);
-                freshvars.push(result_ty.clone());
-                constraints.append(&mut unify_pat(name_src, &result_ty, &pat));
+/// Check that `ty` only refers to declared types applied to the right number of
+/// arguments, and only to the type variables in `params`.
+pub fn check_ty(defs: &TypeDefs, params: &[Ident], ty: &Ty) -> Result<(), String> {
+    match ty {
+        Ty::Int
+        | Ty::Float
+        | Ty::BigInt
+        | Ty::Decimal
+        | Ty::Date
+        | Ty::Time
+        | Ty::Timestamp
+        | Ty::Bytes
+        | Ty::Bool
+        | Ty::Unit
+        | Ty::String => Ok(()),
+        Ty::Tuple(tys) => tys.iter().try_for_each(|ty| check_ty(defs, params, ty)),
+        Ty::Record(recs, row) => {
+            if row.is_some() {
+                return Err(format!(
+                    "Record types in declarations cannot have a row variable, but got {}",
+                    ty
+                ));
             }
-            constraints.push((ty.clone(), Ty::Tuple(freshvars)));
+            recs.iter()
+                .try_for_each(|(_, ty)| check_ty(defs, params, ty))
+        }
+        Ty::Fun(lhs, rhs) => {
+            check_ty(defs, params, lhs)?;
+            check_ty(defs, params, rhs)
+        }
+        Ty::List(ty) => check_ty(defs, params, ty),
+        Ty::Var(ident) => {
+            if params.contains(ident) {
+                Ok(())
+            } else {
+                Err(format!("Type variable {} is not bound", ident))
+            }
+        }
+        Ty::Defined(name, args) => match defs.get(name) {
+            None => Err(format!("Type {} has not been declared", name)),
+            Some(expected) if expected.len() != args.len() => Err(format!(
+                "Type {} expects {} argument(s), but was given {}",
+                name,
+                expected.len(),
+                args.len()
+            )),
+            Some(_) => args.iter().try_for_each(|ty| check_ty(defs, params, ty)),
+        },
+    }
+}
 
-            constraints
+impl<'a> Drop for Inference<'a> {
+    /// Write the bindings of the variables back to `global_sub`
+    fn drop(&mut self) {
+        let idents: Vec<Ident> = self.vars.index.keys().cloned().collect();
+        for ident in idents {
+            match self.find(Ty::Var(ident.clone())) {
+                Ty::Var(var) if var == ident => {}
+                ty => {
+                    self.global_sub.insert(ident, ty);
+                }
+            }
+        }
+    }
+}
+
+/// Check the type of a table against the declared types, whose constructors
+/// are in `env`. The type must be closed, and must not contain functions, since
+/// stored values have to be compared and shown. The columns of a record type
+/// are checked one at a time, so that errors can name the offending column.
+pub fn check_table(defs: &TypeDefs, env: &Env, ty: &Ty) -> Result<(), String> {
+    check_ty(defs, &[], ty)?;
+
+    let columns = match ty {
+        Ty::Record(recs, None) => recs
+            .iter()
+            .map(|(ident, ty)| (format!("column {}", ident), ty))
+            .collect(),
+        ty => vec![("the table".to_string(), ty)],
+    };
+
+    let mut global_sub = GlobalSub::new();
+    let mut name_src = NameSource::new();
+    let newtypes = HashSet::new();
+    let mut inference = Inference::new(&mut global_sub, &mut name_src, &newtypes);
+
+    for (column, ty) in columns {
+        if let Err(fun) = inference.reduce(env, Class::Eq, ty, &mut HashSet::new(), &mut Vec::new())
+        {
+            return Err(if &fun == ty {
+                format!(
+                    "Tables cannot store functions, but {} has type {}",
+                    column, ty
+                )
+            } else {
+                format!(
+                    "Tables cannot store functions, but {} has type {}, which contains {}",
+                    column, ty, fun
+                )
+            });
         }
-        Pattern::Record(recs) => {
-            let mut constraints = Vec::new();
-            let mut freshvars = Vec::new();
+    }
+
+    Ok(())
+}
 
-            for (ident, pat) in recs {
-                // make fresh variables and add that to the unify chain
-                let fresh = name_src.fresh("case");
-                let result_ty = Ty::Var(fresh);
-                freshvars.push((ident.clone(), result_ty.clone()));
-                constraints.append(&mut unify_pat(name_src, &result_ty, &pat));
+/// Check a group of (possibly mutually recursive) union declarations against
+/// the already declared types, returning the type definitions extended with the
+/// new types.
+pub fn check_unions(defs: &TypeDefs, decls: &[UnionDecl]) -> Result<TypeDefs, String> {
+    let mut new_defs = defs.clone();
+
+    for (name, params, _) in decls {
+        check_type_name(&new_defs, name, params)?;
+        new_defs.insert(name.clone(), params.clone());
+    }
+
+    let mut constructors = HashSet::new();
+
+    for (_, params, variants) in decls {
+        for (con, args) in variants {
+            if !constructors.insert(con) {
+                return Err(format!("Constructor {} is declared more than once", con));
             }
-            constraints.push((ty.clone(), Ty::Record(freshvars)));
 
-            constraints
+            for arg in args {
+                check_ty(&new_defs, params, arg)?;
+            }
         }
-        Pattern::Wildcard => vec![],
     }
+
+    Ok(new_defs)
 }
 
-fn instantiate(scheme: &Scheme, name_src: &mut NameSource) -> Ty {
-    let subs = scheme
-        .0
-        .iter()
-        .map(|ident| (ident.clone(), Ty::Var(name_src.fresh(&ident))));
-    let mut res = scheme.1.clone();
-    for sub in subs {
-        res = res.apply(&sub);
+/// Check that a new type `name` with the type parameters `params` can be
+/// declared, i.e. that the name is not taken and the parameters are distinct
+fn check_type_name(defs: &TypeDefs, name: &str, params: &[Ident]) -> Result<(), String> {
+    if matches!(
+        name,
+        "Int"
+            | "Float"
+            | "BigInt"
+            | "Decimal"
+            | "Date"
+            | "Time"
+            | "Timestamp"
+            | "Bytes"
+            | "Bool"
+            | "String"
+            | "List"
+    ) || defs.contains_key(name)
+    {
+        return Err(format!("Type {} has already been declared", name));
     }
-    res
+
+    for (i, param) in params.iter().enumerate() {
+        if params[..i].contains(param) {
+            return Err(format!(
+                "Type parameter {} appears more than once in the declaration of {}",
+                param, name
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check a type alias declaration against the already declared types,
+/// returning the type it stands for with the aliases in it expanded. Aliases
+/// are declared types as well, so other types cannot take their names.
+pub fn check_alias(
+    defs: &TypeDefs,
+    aliases: &Aliases,
+    name: &str,
+    params: &[Ident],
+    ty: &Ty,
+) -> Result<Ty, String> {
+    check_type_name(defs, name, params)?;
+    let ty = expand_aliases(aliases, ty)?;
+    check_ty(defs, params, &ty)?;
+
+    Ok(ty)
+}
+
+/// Replace the type aliases in `ty` by the types they stand for
+pub fn expand_aliases(aliases: &Aliases, ty: &Ty) -> Result<Ty, String> {
+    let expand_all = |tys: &[Ty]| {
+        tys.iter()
+            .map(|ty| expand_aliases(aliases, ty))
+            .collect::<Result<Vec<_>, _>>()
+    };
+
+    match ty {
+        Ty::Tuple(tys) => Ok(Ty::Tuple(expand_all(tys)?)),
+        Ty::Record(recs, row) => Ok(Ty::Record(
+            recs.iter()
+                .map(|(ident, ty)| Ok((ident.clone(), expand_aliases(aliases, ty)?)))
+                .collect::<Result<_, String>>()?,
+            row.clone(),
+        )),
+        Ty::Fun(lhs, rhs) => Ok(Ty::Fun(
+            Box::new(expand_aliases(aliases, lhs)?),
+            Box::new(expand_aliases(aliases, rhs)?),
+        )),
+        Ty::List(ty) => Ok(Ty::List(Box::new(expand_aliases(aliases, ty)?))),
+        Ty::Defined(name, args) => {
+            let args = expand_all(args)?;
+            match aliases.get(name) {
+                None => Ok(Ty::Defined(name.clone(), args)),
+                Some((params, _)) if params.len() != args.len() => Err(format!(
+                    "Type {} expects {} argument(s), but was given {}",
+                    name,
+                    params.len(),
+                    args.len()
+                )),
+                Some((params, ty)) => Ok(substitute_all(ty, params, args)),
+            }
+        }
+        _ => Ok(ty.clone()),
+    }
+}
+
+/// The type schemes of the constructors of a union declaration, e.g. `Cons: a
+/// -> List a -> List a` for `type List a = Nil | Cons a (List a)`.
+pub fn constructor_schemes((name, params, variants): &UnionDecl) -> Vec<(Ident, Scheme)> {
+    let result = Ty::Defined(
+        name.clone(),
+        params.iter().map(|param| Ty::Var(param.clone())).collect(),
+    );
+
+    variants
+        .iter()
+        .map(|(con, args)| {
+            let ty = args.iter().rev().fold(result.clone(), |res, arg| {
+                Ty::Fun(Box::new(arg.clone()), Box::new(res))
+            });
+            (con.clone(), (params.clone(), vec![], ty))
+        })
+        .collect()
 }
 
 pub fn generalize(env: &Env, ty: Ty) -> Scheme {
@@ -264,13 +1890,85 @@ pub fn generalize(env: &Env, ty: Ty) -> Scheme {
         ty.fv()
             .collect::<HashSet<_>>()
             .difference(&env_fvs)
-            .into_iter()
             .cloned()
             .collect(),
+        vec![],
         ty,
     )
 }
 
+/// Substitute `tys` for the variables `vars` in `ty` at once, so a substituted
+/// type mentioning one of the variables is not substituted into again
+fn substitute_all(ty: &Ty, vars: &[Ident], tys: Vec<Ty>) -> Ty {
+    // Rename the variables first. `#` cannot occur in the type variables
+    // written in the source or made by a `NameSource`.
+    let ty = vars.iter().fold(ty.clone(), |ty, var| {
+        ty.apply(&(var.clone(), Ty::Var(format!("#{}", var))))
+    });
+
+    vars.iter()
+        .zip(tys)
+        .fold(ty, |ty, (var, sub)| ty.apply(&(format!("#{}", var), sub)))
+}
+
+/// Rename the quantified variables of a scheme to `a`, `b`, ..., in the order
+/// they occur in its type, instead of the names made during inference
+pub fn rename_scheme((vars, preds, ty): &Scheme) -> Scheme {
+    let free: Vec<_> = ty.fv().filter(|var| !vars.contains(var)).collect();
+
+    let mut old = Vec::new();
+    for var in ty.fv().chain(vars.iter().cloned()) {
+        if vars.contains(&var) && !old.contains(&var) {
+            old.push(var);
+        }
+    }
+
+    let new: Vec<Ident> = var_names()
+        .filter(|name| !free.contains(name))
+        .take(old.len())
+        .collect();
+
+    let renamed = |var: &Ident| match old.iter().position(|x| x == var) {
+        Some(i) => new[i].clone(),
+        None => var.clone(),
+    };
+
+    (
+        new.clone(),
+        preds
+            .iter()
+            .map(|(class, var)| (*class, renamed(var)))
+            .collect(),
+        substitute_all(ty, &old, new.iter().cloned().map(Ty::Var).collect()),
+    )
+}
+
+/// Readable names for type variables: letters, then letters with a number
+fn var_names() -> impl Iterator<Item = Ident> {
+    (0..).map(|i: usize| {
+        let letter = (b'a' + (i % 26) as u8) as char;
+        match i / 26 {
+            0 => letter.to_string(),
+            n => format!("{}{}", letter, n),
+        }
+    })
+}
+
+/// Show the type of a scheme along with its constraints, e.g. `Num a => a ->
+/// a`. The quantified variables are left implicit.
+pub fn fmt_scheme((_, preds, ty): &Scheme) -> String {
+    let preds: Vec<_> = preds
+        .iter()
+        .map(|(class, var)| format!("{} {}", class, var))
+        .collect();
+
+    match preds.len() {
+        0 => format!("{}", ty),
+        1 => format!("{} => {}", preds[0], ty),
+        _ => format!("({}) => {}", preds.join(", "), ty),
+    }
+}
+
 trait Substitute {
     fn apply(&self, substitution: &Substitution) -> Self;
 }
@@ -279,15 +1977,35 @@ impl Substitute for Ty {
     fn apply(&self, substitution: &Substitution) -> Self {
         match self {
             Ty::Int => Ty::Int,
+            Ty::Float => Ty::Float,
+            Ty::BigInt => Ty::BigInt,
+            Ty::Decimal => Ty::Decimal,
+            Ty::Date => Ty::Date,
+            Ty::Time => Ty::Time,
+            Ty::Timestamp => Ty::Timestamp,
+            Ty::Bytes => Ty::Bytes,
             Ty::Bool => Ty::Bool,
             Ty::Tuple(tys) => Ty::Tuple(tys.iter().map(|x| x.apply(substitution)).collect()),
             Ty::Unit => Ty::Unit,
             Ty::String => Ty::String,
-            Ty::Record(recs) => Ty::Record(
-                recs.iter()
+            Ty::Record(recs, row) => {
+                let mut recs: Vec<_> = recs
+                    .iter()
                     .map(|(ident, ty)| (ident.clone(), ty.apply(substitution)))
-                    .collect(),
-            ),
+                    .collect();
+
+                match row {
+                    Some(row) if row == &substitution.0 => match &substitution.1 {
+                        Ty::Var(var) => Ty::Record(recs, Some(var.clone())),
+                        Ty::Record(more, rest) => {
+                            recs.extend(more.iter().cloned());
+                            Ty::Record(recs, rest.clone())
+                        }
+                        other => unreachable!("{}", other),
+                    },
+                    _ => Ty::Record(recs, row.clone()),
+                }
+            }
             Ty::Var(ident) => {
                 if ident == &substitution.0 {
                     substitution.1.clone()
@@ -303,66 +2021,7 @@ impl Substitute for Ty {
                 name.clone(),
                 args.iter().map(|x| x.apply(substitution)).collect(),
             ),
-        }
-    }
-}
-
-impl Substitute for Constraint {
-    fn apply(&self, substitution: &Substitution) -> Self {
-        (self.0.apply(substitution), self.1.apply(substitution))
-    }
-}
-
-trait SubstituteMut {
-    fn apply_mut(&mut self, substitution: &Substitution);
-}
-
-impl SubstituteMut for Ty {
-    fn apply_mut(&mut self, substitution: &Substitution) {
-        match self {
-            Ty::Tuple(tys) => {
-                for ty in tys.iter_mut() {
-                    ty.apply_mut(substitution)
-                }
-            }
-            Ty::Record(recs) => {
-                for (_, ty) in recs.iter_mut() {
-                    ty.apply_mut(substitution)
-                }
-            }
-            Ty::Var(ident) => {
-                if ident == &substitution.0 {
-                    *self = substitution.1.clone();
-                }
-            }
-            Ty::Fun(lhs, rhs) => {
-                lhs.apply_mut(substitution);
-                rhs.apply_mut(substitution);
-            }
-            _ => (),
-        }
-    }
-}
-
-impl SubstituteMut for Constraint {
-    fn apply_mut(&mut self, substitution: &Substitution) {
-        self.0.apply_mut(substitution);
-        self.1.apply_mut(substitution);
-    }
-}
-
-impl SubstituteMut for Scheme {
-    fn apply_mut(&mut self, substitution: &Substitution) {
-        if !self.0.contains(&substitution.0) {
-            self.1.apply_mut(substitution);
-        }
-    }
-}
-
-impl SubstituteMut for Env {
-    fn apply_mut(&mut self, substitution: &Substitution) {
-        for x in self.values_mut() {
-            x.apply_mut(substitution);
+            Ty::List(ty) => Ty::List(Box::new(ty.apply(substitution))),
         }
     }
 }
@@ -376,26 +2035,38 @@ impl FreeVars for Ty {
     fn fv(&self) -> Box<dyn Iterator<Item = Ident> + '_> {
         match self {
             Ty::Int => Box::new(iter::empty()),
+            Ty::Float => Box::new(iter::empty()),
+            Ty::BigInt => Box::new(iter::empty()),
+            Ty::Decimal => Box::new(iter::empty()),
+            Ty::Date => Box::new(iter::empty()),
+            Ty::Time => Box::new(iter::empty()),
+            Ty::Timestamp => Box::new(iter::empty()),
+            Ty::Bytes => Box::new(iter::empty()),
             Ty::Bool => Box::new(iter::empty()),
             Ty::Tuple(tys) => Box::new(tys.iter().flat_map(|x| x.fv())),
             Ty::Unit => Box::new(iter::empty()),
             Ty::String => Box::new(iter::empty()),
-            Ty::Record(recs) => Box::new(recs.iter().flat_map(|(_, x)| x.fv())),
+            Ty::Record(recs, row) => Box::new(
+                recs.iter()
+                    .flat_map(|(_, x)| x.fv())
+                    .chain(row.iter().cloned()),
+            ),
             Ty::Var(ident) => Box::new(iter::once(ident.clone())),
             Ty::Fun(lhs, rhs) => Box::new(lhs.fv().chain(rhs.fv())),
             Ty::Defined(_, args) => Box::new(args.iter().flat_map(|arg| arg.fv())),
+            Ty::List(ty) => ty.fv(),
         }
     }
 }
 
 impl FreeVars for Scheme {
     fn fv(&self) -> Box<dyn Iterator<Item = Ident> + '_> {
-        Box::new(self.1.fv().filter(move |x| !self.0.contains(x)))
+        Box::new(self.2.fv().filter(move |x| !self.0.contains(x)))
     }
 }
 
 impl FreeVars for Env {
     fn fv(&self) -> Box<dyn Iterator<Item = Ident> + '_> {
-        Box::new(self.iter().flat_map(|(_, x)| x.fv()))
+        Box::new(self.values().flat_map(|x| x.fv()))
     }
 }
@@ -30,6 +30,7 @@ fn scheme_fv() {
         vec!("x".to_string()),
         (
             vec!("y".to_string()),
+            vec!(),
             Tuple(vec!(Var("x".to_string()), Var("y".to_string())))
         )
             .fv()
@@ -39,6 +40,7 @@ fn scheme_fv() {
         vec!("y".to_string()),
         (
             vec!("x".to_string()),
+            vec!(),
             Fun(
                 Box::new(Var("x".to_string())),
                 Box::new(Var("y".to_string()))
@@ -59,6 +61,7 @@ fn env_fv() {
             "foo".to_string(),
             (
                 vec!("y".to_string()),
+                vec!(),
                 Tuple(vec!(Var("x".to_string()), Var("y".to_string())))
             )
         )]
@@ -127,7 +130,7 @@ fn unify() {
 
     assert!(
         super::unify(vec!((Fun(Box::new(Int), Box::new(Int)), Int)).into_iter())
-            .collect::<Result<Vec<_>, std::string::String>>()
+            .collect::<Result<Vec<_>, TypeError>>()
             .is_err()
     );
 
@@ -159,11 +162,14 @@ fn unify() {
         Ok(vec!(("a".to_string(), Int))),
         super::unify(
             vec!((
-                Record(vec!(("x".to_string(), Int), ("y".to_string(), Int))),
-                Record(vec!(
-                    ("x".to_string(), Var("a".to_string())),
-                    ("y".to_string(), Var("a".to_string()))
-                ))
+                Record(vec!(("x".to_string(), Int), ("y".to_string(), Int)), None),
+                Record(
+                    vec!(
+                        ("x".to_string(), Var("a".to_string())),
+                        ("y".to_string(), Var("a".to_string()))
+                    ),
+                    None
+                )
             ))
             .into_iter()
         )
@@ -174,16 +180,88 @@ fn unify() {
         Ok(vec!(("b".to_string(), Bool), ("a".to_string(), Int))),
         super::unify(
             vec!((
-                Record(vec!(("x".to_string(), Int), ("y".to_string(), Bool))),
-                Record(vec!(
-                    ("x".to_string(), Var("a".to_string())),
-                    ("y".to_string(), Var("b".to_string()))
-                ))
+                Record(vec!(("x".to_string(), Int), ("y".to_string(), Bool)), None),
+                Record(
+                    vec!(
+                        ("x".to_string(), Var("a".to_string())),
+                        ("y".to_string(), Var("b".to_string()))
+                    ),
+                    None
+                )
+            ))
+            .into_iter()
+        )
+        .collect()
+    );
+
+    assert_eq!(
+        Ok(vec!(
+            ("a".to_string(), Int),
+            ("r".to_string(), Record(vec!(("y".to_string(), Bool)), None))
+        )),
+        super::unify(
+            vec!((
+                Record(vec!(("x".to_string(), Int)), Some("r".to_string())),
+                Record(
+                    vec!(
+                        ("x".to_string(), Var("a".to_string())),
+                        ("y".to_string(), Bool)
+                    ),
+                    None
+                )
             ))
             .into_iter()
         )
         .collect()
     );
+
+    assert!(super::unify(
+        vec!((
+            Record(vec!(("x".to_string(), Int)), Some("r".to_string())),
+            Record(vec!(("y".to_string(), Bool)), None)
+        ))
+        .into_iter()
+    )
+    .collect::<Result<Vec<_>, TypeError>>()
+    .is_err());
+}
+
+#[test]
+fn unify_classes() {
+    use Ty::*;
+
+    let var = |x: &str| Var(x.to_string());
+
+    // Merging two classes of variables and then binding one of them binds all
+    assert_eq!(
+        Ok(vec!(
+            ("d".to_string(), Int),
+            ("b".to_string(), Int),
+            ("c".to_string(), Int),
+            ("a".to_string(), Int)
+        )),
+        super::unify(
+            vec!(
+                (var("a"), var("b")),
+                (var("c"), var("d")),
+                (var("a"), var("c")),
+                (var("d"), Int)
+            )
+            .into_iter()
+        )
+        .collect()
+    );
+
+    assert!(super::unify(
+        vec!(
+            (var("a"), var("b")),
+            (var("b"), var("c")),
+            (var("c"), Fun(Box::new(Int), Box::new(var("a"))))
+        )
+        .into_iter()
+    )
+    .collect::<Result<Vec<_>, TypeError>>()
+    .is_err());
 }
 
 #[test]
@@ -196,8 +274,13 @@ fn infer() {
         super::infer(
             &mut HashMap::new(),
             &mut NameSource::new(),
-            &mut HashMap::new(),
-            &Expr::Lambda("a".to_string(), Box::new(Expr::Ident("a".to_string())))
+            &HashMap::new(),
+            &HashSet::new(),
+            &Expr::Lambda(
+                "a".to_string(),
+                None,
+                Box::new(Expr::Ident("a".to_string()))
+            )
         )
     );
 
@@ -206,9 +289,10 @@ fn infer() {
         super::infer(
             &mut HashMap::new(),
             &mut NameSource::new(),
-            &mut HashMap::new(),
+            &HashMap::new(),
+            &HashSet::new(),
             &Expr::Let(
-                vec!(("x".to_string(), Expr::Atom(Atom::Int(42)))),
+                vec!((Pattern::Ident("x".to_string()), Expr::Atom(Atom::Int(42)))),
                 Box::new(Expr::Ident("x".to_string()))
             )
         )
@@ -219,11 +303,15 @@ fn infer() {
         super::infer(
             &mut HashMap::new(),
             &mut NameSource::new(),
-            &mut HashMap::new(),
+            &HashMap::new(),
+            &HashSet::new(),
             &Expr::Let(
                 vec!(
-                    ("x".to_string(), Expr::Atom(Atom::Int(42))),
-                    ("y".to_string(), Expr::Atom(Atom::Bool(true)))
+                    (Pattern::Ident("x".to_string()), Expr::Atom(Atom::Int(42))),
+                    (
+                        Pattern::Ident("y".to_string()),
+                        Expr::Atom(Atom::Bool(true))
+                    )
                 ),
                 Box::new(Expr::Ident("y".to_string()))
             )
@@ -239,11 +327,16 @@ fn infer() {
         super::infer(
             &mut HashMap::new(),
             &mut NameSource::new(),
-            &mut HashMap::new(),
+            &HashMap::new(),
+            &HashSet::new(),
             &Expr::Let(
                 vec!((
-                    "id".to_string(),
-                    Expr::Lambda("y".to_string(), Box::new(Expr::Ident("y".to_string())))
+                    Pattern::Ident("id".to_string()),
+                    Expr::Lambda(
+                        "y".to_string(),
+                        None,
+                        Box::new(Expr::Ident("y".to_string()))
+                    )
                 )),
                 Box::new(Expr::Ident("id".to_string()))
             )
@@ -251,21 +344,24 @@ fn infer() {
     );
 
     // `let apply = 𝜆f . 𝜆x . f x in let id = 𝜆y . y in apply id`
-    let mut env = HashMap::new();
+    let env = HashMap::new();
     let mut subs = HashMap::new();
 
     let res = super::infer(
         &mut subs,
         &mut NameSource::new(),
-        &mut env,
+        &env,
+        &HashSet::new(),
         &Expr::Let(
             vec![
                 (
-                    "apply".to_string(),
+                    Pattern::Ident("apply".to_string()),
                     Expr::Lambda(
                         "f".to_string(),
+                        None,
                         Box::new(Expr::Lambda(
                             "x".to_string(),
+                            None,
                             Box::new(Expr::Apply(
                                 Box::new(Expr::Ident("f".to_string())),
                                 Box::new(Expr::Ident("x".to_string())),
@@ -274,8 +370,12 @@ fn infer() {
                     ),
                 ),
                 (
-                    "id".to_string(),
-                    Expr::Lambda("y".to_string(), Box::new(Expr::Ident("y".to_string()))),
+                    Pattern::Ident("id".to_string()),
+                    Expr::Lambda(
+                        "y".to_string(),
+                        None,
+                        Box::new(Expr::Ident("y".to_string())),
+                    ),
                 ),
             ],
             Box::new(Expr::Apply(
@@ -306,7 +406,8 @@ fn infer_and_print() {
         let ty = super::infer(
             &mut HashMap::new(),
             &mut NameSource::new(),
-            &mut HashMap::new(),
+            &HashMap::new(),
+            &HashSet::new(),
             &e,
         )
         .unwrap();
@@ -316,18 +417,15 @@ fn infer_and_print() {
 
     assert_eq!("Int", infer("4"));
 
-    assert_eq!(
-        "(x_0_1 -> x_0_1)",
-        infer("let id = lambda x -> x in id end")
-    );
+    assert_eq!("x_0_1 -> x_0_1", infer("let id = lambda x -> x in id end"));
 
     assert_eq!(
-        "((x_0_1 -> x_0_1), Int, Bool)",
+        "(x_0_1 -> x_0_1, Int, Bool)",
         infer("(let id = lambda x -> x in id end, 42, True)")
     );
 
     assert_eq!(
-        "{ x: (x_0_1 -> x_0_1), y: Int, z: Bool }",
+        "{ x: x_0_1 -> x_0_1, y: Int, z: Bool }",
         infer("{ x = let id = lambda x -> x in id end, y = 42, z = True }")
     );
 
@@ -335,6 +433,19 @@ fn infer_and_print() {
 
     assert_eq!("String", infer("\"Hello World!\""));
 
+    assert_eq!("Float", infer("3.14"));
+
+    assert_eq!("(BigInt, Decimal)", infer("(1n, 1.0d)"));
+
+    assert_eq!(
+        "(Date, Time, Timestamp)",
+        infer("(@2026-10-17, @12:30, @2026-10-17T12:30:00Z)")
+    );
+
+    assert_eq!("Bytes", infer("base64\"AA==\""));
+
+    assert_eq!("Int", infer("case 1.5 of 1.5 => 1 | _ => 2 end"));
+
     assert_eq!("Int", infer("case 42 of i => i end"));
 
     assert_eq!("Int", infer("case (42, True) of (i, j) => i end"));
@@ -345,12 +456,164 @@ fn infer_and_print() {
         "Int",
         infer("case { x = 42, y = True } of { x = i, y = _ } => i end")
     );
+
+    assert_eq!("Int", infer("if True then 1 else 2"));
+
+    assert_eq!("Bool -> Bool", infer("lambda b -> if b then False else b"));
+
+    assert_eq!("Int", infer("{ x = 42, y = True }.x"));
+
+    assert_eq!(
+        "{ x: Int, y: Bool }",
+        infer("{ { x = 42, y = True } with x = 43 }")
+    );
+
+    assert_eq!(
+        "Bool",
+        infer("let r = { x = 42, y = True } in { r with y = False }.y end")
+    );
+
+    assert_eq!(
+        "{ name: field_1, ..row_2 } -> field_1",
+        infer("lambda r -> r.name")
+    );
+
+    assert_eq!(
+        "(String, Int)",
+        infer(
+            "let name = lambda r -> r.name \
+             in (name { name = \"a\", age = 1 }, name { admin = True, name = 2 }) end"
+        )
+    );
+
+    assert_eq!(
+        "{ x: field_1, y: field_3, ..row_5 } -> (field_1, field_3)",
+        infer("lambda r -> (r.x, r.y)")
+    );
+
+    assert_eq!(
+        "{ x: Int, ..row_2 } -> { x: Int, ..row_2 }",
+        infer("lambda r -> { r with x = 1 }")
+    );
+
+    assert_eq!(
+        "{ x: case_1, ..row_4 } -> case_1",
+        infer("lambda r -> case r of { x, .. } => x end")
+    );
+
+    assert_eq!(
+        "{ x: Int, ..s_0 } -> { x: Int, ..s_0 }",
+        infer("lambda (r : { x : Int, ..s }) -> r")
+    );
+
+    assert_eq!(
+        "{ x: Int, y: Bool } -> Int",
+        infer("(lambda r -> r.x : { x : Int, y : Bool } -> Int)")
+    );
+
+    assert_eq!(
+        "(Int, Bool)",
+        infer("let rec id = lambda x -> x in (id 1, id True) end")
+    );
+
+    assert_eq!(
+        "Bool -> Int",
+        infer("let rec f = lambda b -> if b then f False else 42 in f end")
+    );
+
+    assert_eq!(
+        "Bool -> Int",
+        infer(
+            "let rec ping = lambda b -> if b then pong b else 1 \
+             and pong = lambda b -> ping False in pong end"
+        )
+    );
+
+    assert_eq!("[Int]", infer("[1, 2, 3]"));
+
+    assert_eq!("[[Bool]]", infer("[[True], []]"));
+
+    assert_eq!("[Int]", infer("1 :: 2 :: []"));
+
+    assert_eq!(
+        "[Int] -> Int",
+        infer("lambda l -> case l of [] => 0 | [x] => x | x :: _ => x end")
+    );
+
+    assert_eq!(
+        "Int",
+        infer(
+            "let rec sum = lambda l -> case l of [] => 0 | x :: xs => sum xs end \
+             in sum [1, 2] end"
+        )
+    );
+
+    assert_eq!(
+        "[x_5] -> x_5",
+        infer("lambda l -> case l of [x] | [_, x] => x | (x :: _) as xs => x end")
+    );
+
+    assert_eq!(
+        "Int",
+        infer("case { name = 1, age = True } of { name, .. } => name end")
+    );
+
+    assert_eq!(
+        "Bool",
+        infer(
+            "case ({ a = { b = 1, c = True }, d = () }, 1) of ({ a = { c, .. }, .. }, _) => c end"
+        )
+    );
+
+    assert_eq!(
+        "(Bool, Int)",
+        infer("let (a, { x = px, .. }) = (1, { x = True, y = () }) in (px, a) end")
+    );
+
+    assert_eq!(
+        "(Int, Bool)",
+        infer("let (id, n) = (lambda x -> x, 1) in (id n, id True) end")
+    );
+
+    assert_eq!("a_0 -> a_0", infer("(lambda x -> x : a -> a)"));
+
+    assert_eq!(
+        "a_0 -> a_1 -> a_0",
+        infer("lambda (x : a) -> lambda (y : a) -> (x : b)")
+    );
+
+    assert_eq!(
+        "{ x: Int, y: Bool } -> Bool",
+        infer("lambda (r : { x : Int, y : Bool }) -> r.y")
+    );
+
+    assert_eq!(
+        "Int -> { x: Int } -> Int",
+        infer("let f : Int -> { x : Int } -> Int = lambda n -> lambda r -> r.x in f end")
+    );
+
+    assert_eq!(
+        "(Bool, ())",
+        infer("(lambda (a, b) c -> (b, c)) (1, True) ()")
+    );
+
+    assert_eq!(
+        "Int",
+        infer("let rec len l = case l of [] => 0 | _ :: xs => len xs end in len [True] end")
+    );
+
+    assert_eq!("Int -> Int", infer("let f x : Int = x in f end"));
+
+    assert_eq!(
+        "(Int, Bool) -> Int",
+        infer("lambda p -> case p of (x, b) when b => x | (0 | 1, _) => 1 | _ => 0 end")
+    );
 }
 
 #[test]
 fn infer_fail() {
     use pest::Parser;
-    fn infer(input: &str) -> Result<Ty, String> {
+    fn infer(input: &str) -> Result<Ty, TypeError> {
         let e = crate::parse::parse_exprs(
             crate::parse::Parser::parse(crate::parse::Rule::expr, input)
                 .unwrap_or_else(|e| panic!("{}", e))
@@ -362,7 +625,8 @@ fn infer_fail() {
         super::infer(
             &mut HashMap::new(),
             &mut NameSource::new(),
-            &mut HashMap::new(),
+            &HashMap::new(),
+            &HashSet::new(),
             &e,
         )
     }
@@ -389,12 +653,102 @@ fn infer_fail() {
     assert!(infer("case (1337, 42) of (0, False) => True | (1337, i) => i end").is_err());
 
     assert!(infer("case (1337, 42) of (0, False) => True | (1337, i) => False end").is_err());
+
+    assert!(infer("if 1 then 2 else 3").is_err());
+
+    assert!(infer("if True then 2 else False").is_err());
+
+    assert!(infer("lambda x -> if x then x 1 else 2").is_err());
+
+    assert!(infer("{ x = 42 }.y").is_err());
+
+    assert!(infer("(1, 2).x").is_err());
+
+    assert!(infer("{ { x = 42 } with x = True }").is_err());
+
+    assert!(infer("{ { x = 42 } with y = 1 }").is_err());
+
+    assert!(infer("let rec x = 1 in x end").is_err());
+
+    assert!(infer("let rec f = lambda x -> (f 1, f True) in f end").is_err());
+
+    assert!(infer("let f = lambda x -> f x in f end").is_err());
+
+    assert!(infer("[1, True]").is_err());
+
+    assert!(infer("[1, 1.0]").is_err());
+
+    assert!(infer("[1, 1n]").is_err());
+
+    assert!(infer("[@2026-10-17, @2026-10-17T12:30:00Z]").is_err());
+
+    assert!(infer("[hex\"00\", \"00\"]").is_err());
+
+    assert!(infer("case 1.0 of 1.0d => 1 end").is_err());
+
+    assert!(infer("case 1 of 1.0 => 1 end").is_err());
+
+    assert!(infer("1 :: [True]").is_err());
+
+    assert!(infer("1 :: 2").is_err());
+
+    assert!(infer("case [1] of [True] => 1 end").is_err());
+
+    assert!(infer("case [1] of x :: 2 => x end").is_err());
+
+    assert!(infer("case (1, 2) of (x, _) | (_, y) => 0 end").is_err());
+
+    assert!(infer("case (1, True) of (x, _) | (_, x) => 0 end").is_err());
+
+    assert!(infer("case 1 of 1 | True => 0 end").is_err());
+
+    assert!(infer("case (1, True) of (x, _) as x => x end").is_err());
+
+    assert!(infer("case 1 of x when x => 0 end").is_err());
+
+    assert!(infer("case { x = 1, y = 2 } of { x } => x end").is_err());
+
+    assert!(infer("case { x = 1, y = 2 } of { z, .. } => z end").is_err());
+
+    assert!(infer("case { x = 1, y = 2 } of { x = True, .. } => x end").is_err());
+
+    assert!(infer("lambda r -> if r.x then r.x else 1").is_err());
+
+    assert!(infer("lambda r -> ({ r with x = 1 }, { r with x = True })").is_err());
+
+    assert!(infer("(lambda r -> r.y : { x : Int } -> Int)").is_err());
+
+    assert!(infer("(lambda x -> x.a : { a : Int, ..r } -> r)").is_err());
+
+    assert!(infer("lambda (1, x) -> x").is_err());
+
+    assert!(infer("(lambda (a, b) -> a) 1").is_err());
+
+    assert!(infer("(1 : Bool)").is_err());
+
+    assert!(infer("(lambda x -> x : a -> Int) True").is_err());
+
+    assert!(infer("((lambda x -> x : a -> a) : Int -> Bool)").is_err());
+
+    assert!(infer("let f : Int -> Int = lambda x -> True in f end").is_err());
+
+    assert!(infer("lambda (x : Int) -> if x then 1 else 2").is_err());
+
+    assert!(infer("let (a, b) = 1 in a end").is_err());
+
+    assert!(infer("let 1 = 1 in 2 end").is_err());
+
+    assert!(infer("let [x] = [1] in x end").is_err());
+
+    assert!(infer("let x :: _ = [1] in x end").is_err());
+
+    assert!(infer("let { x } = { x = 1, y = 2 } in x end").is_err());
 }
 
 #[test]
 fn infer_fail2() {
     use pest::Parser;
-    fn infer(input: &str) -> Result<Ty, String> {
+    fn infer(input: &str) -> Result<Ty, TypeError> {
         let e = crate::parse::parse_exprs(
             crate::parse::Parser::parse(crate::parse::Rule::expr, input)
                 .unwrap_or_else(|e| panic!("{}", e))
@@ -406,10 +760,764 @@ fn infer_fail2() {
         super::infer(
             &mut HashMap::new(),
             &mut NameSource::new(),
-            &mut HashMap::new(),
+            &HashMap::new(),
+            &HashSet::new(),
             &e,
         )
     }
 
     assert!(infer("case 42 of 41 => () | 42 => 42 end").is_err());
 }
+
+fn union_decls(input: &str) -> Vec<UnionDecl> {
+    match crate::parse::parse(input).unwrap() {
+        Statement::Union(decls) => decls,
+        stm => panic!("Not a type declaration: {}", stm),
+    }
+}
+
+fn parse_expr(input: &str) -> Expr {
+    use pest::Parser;
+    crate::parse::parse_exprs(
+        crate::parse::Parser::parse(crate::parse::Rule::expr, input)
+            .unwrap_or_else(|e| panic!("{}", e))
+            .next()
+            .unwrap()
+            .into_inner(),
+    )
+    .unwrap()
+}
+
+/// Infer the type of `input` in `env`, without any newtypes
+fn infer_str(env: &Env, input: &str) -> Result<Ty, TypeError> {
+    super::infer(
+        &mut HashMap::new(),
+        &mut NameSource::new(),
+        env,
+        &HashSet::new(),
+        &parse_expr(input),
+    )
+}
+
+#[test]
+fn check_unions() {
+    fn check(input: &str) -> Result<TypeDefs, String> {
+        super::check_unions(&HashMap::new(), &union_decls(input))
+    }
+
+    assert!(check("type Seq a = Nil | Cons a (Seq a)").is_ok());
+
+    assert!(check("type Tree = Leaf | Node Forest and Forest = Empty | Trees Tree Forest").is_ok());
+
+    assert!(
+        check("type Rose a = Rose a (Forest a) and Forest a = Nil | Cons (Rose a) (Forest a)")
+            .is_ok()
+    );
+
+    assert!(check("type Option a = None | Some (Int -> a)").is_ok());
+
+    // Undeclared type
+    assert!(check("type Tree = Leaf | Node Forest").is_err());
+
+    // Wrong number of type arguments
+    assert!(check("type Seq a = Nil | Cons a Seq").is_err());
+
+    assert!(check("type Seq a = Nil | Cons a (Seq a a)").is_err());
+
+    // Unbound type variable
+    assert!(check("type Seq = Nil | Cons a Seq").is_err());
+
+    // Duplicate constructors and types
+    assert!(check("type A = C and B = C").is_err());
+
+    assert!(check("type A = C and A = D").is_err());
+
+    assert!(check("type Int = I").is_err());
+
+    assert!(check("type Pair a a = Pair a a").is_err());
+
+    assert!(check("type Named r = Named { name : String, ..r }").is_err());
+
+    let defs = check("type Seq a = Nil | Cons a (Seq a)").unwrap();
+
+    assert!(super::check_unions(&defs, &union_decls("type Seq = L")).is_err());
+
+    assert!(super::check_unions(&defs, &union_decls("type Ints = Ints (Seq Int)")).is_ok());
+}
+
+#[test]
+fn check_table() {
+    fn check(decls: &str, table: &str) -> Result<(), String> {
+        let mut defs = HashMap::new();
+        let mut env = HashMap::new();
+        for decl in decls.lines() {
+            let decls = union_decls(decl);
+            defs = super::check_unions(&defs, &decls).unwrap();
+            for decl in decls {
+                env.extend(constructor_schemes(&decl));
+            }
+        }
+
+        match crate::parse::parse(&format!("create table t {}", table)).unwrap() {
+            Statement::Create(_, def) => super::check_table(&defs, &env, &def.ty),
+            stm => panic!("Not a table declaration: {}", stm),
+        }
+    }
+
+    let decls = "type Seq a = Nil | Cons a (Seq a)\ntype Handler = Handler (Int -> Int)";
+
+    assert_eq!(Ok(()), check(decls, "{ id: Int, tags: Seq String }"));
+
+    assert_eq!(Ok(()), check(decls, "[(Seq (Seq Bool), ())]"));
+
+    // Undeclared types and wrong numbers of type arguments
+    assert_eq!(
+        Err("Type User has not been declared".to_string()),
+        check(decls, "{ owner: User }")
+    );
+
+    assert!(check(decls, "Seq").is_err());
+
+    // Type variables, which would let any value be inserted
+    assert_eq!(
+        Err("Type variable a is not bound".to_string()),
+        check(decls, "a")
+    );
+
+    assert!(check(decls, "Seq a").is_err());
+
+    assert!(check(decls, "{ name: String, ..r }").is_err());
+
+    // Functions, also inside declared types
+    assert_eq!(
+        Err("Tables cannot store functions, but column f has type Int -> Int".to_string()),
+        check(decls, "{ id: Int, f: Int -> Int }")
+    );
+
+    assert_eq!(
+        Err(
+            "Tables cannot store functions, but column h has type [Handler], which contains \
+             Int -> Int"
+                .to_string()
+        ),
+        check(decls, "{ h: [Handler] }")
+    );
+
+    assert_eq!(
+        Err("Tables cannot store functions, but the table has type Int -> Int".to_string()),
+        check(decls, "Int -> Int")
+    );
+
+    assert_eq!(
+        Err(
+            "Tables cannot store functions, but the table has type Seq Handler, which contains \
+             Int -> Int"
+                .to_string()
+        ),
+        check(decls, "Seq Handler")
+    );
+}
+
+#[test]
+fn infer_constructors() {
+    fn infer(decl: &str, input: &str) -> Result<String, TypeError> {
+        let mut env = HashMap::new();
+        for decl in union_decls(decl) {
+            env.extend(constructor_schemes(&decl));
+        }
+
+        infer_str(&env, input).map(|ty| format!("{}", ty))
+    }
+
+    let seq = "type Seq a = Nil | Cons a (Seq a)";
+
+    assert_eq!(Ok("Seq Int".to_string()), infer(seq, "Cons 1 (Cons 2 Nil)"));
+
+    assert_eq!(
+        Ok("(Seq Int) -> Int".to_string()),
+        infer(seq, "lambda l -> case l of Cons x xs => x | Nil => 0 end")
+    );
+
+    assert_eq!(
+        Ok("(Int, Bool)".to_string()),
+        infer(
+            seq,
+            "let rec last = lambda l -> case l of Cons x Nil => x | Cons _ xs => last xs end \
+             in (last (Cons 1 Nil), last (Cons True Nil)) end"
+        )
+    );
+
+    assert!(infer(seq, "Cons 1 (Cons True Nil)").is_err());
+
+    assert!(infer(seq, "case Nil of Cons x => x end").is_err());
+
+    assert!(infer(seq, "case Nil of Cons x xs ys => x end").is_err());
+
+    assert!(infer(seq, "case Nil of Foo => 1 end").is_err());
+
+    let tree = "type Tree = Leaf | Node Forest and Forest = Empty | Trees Tree Forest";
+
+    assert_eq!(
+        Ok("Tree".to_string()),
+        infer(tree, "Node (Trees Leaf (Trees Leaf Empty))")
+    );
+
+    assert!(infer(tree, "Node (Trees Leaf Leaf)").is_err());
+}
+
+#[test]
+fn newtype_patterns() {
+    fn infer(input: &str) -> Result<String, TypeError> {
+        let mut env = HashMap::new();
+        for decl in union_decls("type UserId = UserId Int and Id = Id Int") {
+            env.extend(constructor_schemes(&decl));
+        }
+        let newtypes = vec!["UserId".to_string()].into_iter().collect();
+
+        super::infer(
+            &mut HashMap::new(),
+            &mut NameSource::new(),
+            &env,
+            &newtypes,
+            &parse_expr(input),
+        )
+        .map(|ty| format!("{}", ty))
+    }
+
+    assert_eq!(
+        Ok("Int".to_string()),
+        infer("let uid = UserId 1 in let UserId n = uid in n end end")
+    );
+
+    assert_eq!(
+        Ok("(UserId, Bool) -> Int".to_string()),
+        infer("lambda p -> let (UserId n, b) = p in if b then n else 0 end")
+    );
+
+    // Only the constructor of a newtype is irrefutable, not its argument or
+    // the constructors of other types
+    assert!(matches!(
+        infer("let UserId 1 = UserId 1 in 2 end"),
+        Err(TypeError::Refutable(_))
+    ));
+    assert!(matches!(
+        infer("let Id n = Id 1 in n end"),
+        Err(TypeError::Refutable(_))
+    ));
+}
+
+#[test]
+fn infer_large() {
+    // Matching a large tuple against a pattern gives one constraint per
+    // component, which should not be a problem
+    let n = 5000;
+    let e = Expr::Case(
+        Box::new(Expr::Tuple(vec![Expr::Atom(Atom::Int(1)); n])),
+        vec![(
+            Pattern::Tuple((0..n).map(|i| Pattern::Ident(format!("x{}", i))).collect()),
+            None,
+            Expr::Ident("x0".to_string()),
+        )],
+    );
+
+    assert_eq!(
+        Ok(Ty::Int),
+        super::infer(
+            &mut HashMap::new(),
+            &mut NameSource::new(),
+            &Env::new(),
+            &HashSet::new(),
+            &e
+        )
+    );
+}
+
+#[test]
+fn infer_deep() {
+    // `1 + (1 + ... (1 + 1))`, nested `n` applications of `+` deep
+    fn sum(n: usize) -> Expr {
+        let mut e = Expr::Atom(Atom::Int(1));
+        for _ in 0..n {
+            e = Expr::Apply(
+                Box::new(Expr::Apply(
+                    Box::new(Expr::Ident("+".to_string())),
+                    Box::new(Expr::Atom(Atom::Int(1))),
+                )),
+                Box::new(e),
+            );
+        }
+        e
+    }
+
+    fn infer(e: &Expr) -> Result<Ty, TypeError> {
+        let mut env = Env::new();
+        for (name, scheme, _) in crate::prelude::builtins() {
+            env.insert(name, scheme);
+        }
+
+        super::infer(
+            &mut HashMap::new(),
+            &mut NameSource::new(),
+            &env,
+            &HashSet::new(),
+            e,
+        )
+    }
+
+    // Deep expressions are fine on the default stack of a thread
+    assert_eq!(Ok(Ty::Int), infer(&sum(100)));
+
+    // The operands of the innermost `+` are nested `n + 2` expressions deep,
+    // so this is just within the limit, which the db thread has room for
+    let deep = sum(MAX_DEPTH - 2);
+    let too_deep = sum(MAX_DEPTH - 1);
+    std::thread::Builder::new()
+        .stack_size(crate::db::STACK_SIZE)
+        .spawn(move || {
+            assert_eq!(Ok(Ty::Int), infer(&deep));
+            assert_eq!(Err(TypeError::TooDeep), infer(&too_deep));
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn type_errors() {
+    let err = infer_str(&Env::new(), "if 1 then 2 else 3").unwrap_err();
+    assert_eq!(
+        TypeError::Mismatch {
+            expected: Ty::Bool,
+            actual: Ty::Int,
+            sources: vec![(Expr::Atom(Atom::Int(1)), Ty::Int)],
+        },
+        err
+    );
+    assert_eq!(
+        "Type mismatch: expected Bool, but found Int\n  `1` has type Int",
+        err.to_string()
+    );
+
+    assert_eq!(
+        Err(TypeError::Mismatch {
+            expected: Ty::Int,
+            actual: Ty::Bool,
+            sources: vec![(Expr::Atom(Atom::Bool(true)), Ty::Bool)],
+        }),
+        infer_str(&Env::new(), "[1, True]")
+    );
+
+    // The first arm determines the expected type of the others
+    assert_eq!(
+        Err(TypeError::Mismatch {
+            expected: Ty::Int,
+            actual: Ty::String,
+            sources: vec![(Expr::Atom(Atom::String("b".to_string())), Ty::String)],
+        }),
+        infer_str(&Env::new(), "case 1 of 1 => 2 | _ => \"b\" end")
+    );
+
+    match infer_str(
+        &Env::new(),
+        "if True then { x = 1, z = 2 } else { y = 1, z = 2 }",
+    ) {
+        Err(TypeError::Fields { missing, extra, .. }) => {
+            assert_eq!(vec!["x".to_string()], missing);
+            assert_eq!(vec!["y".to_string()], extra);
+        }
+        res => panic!("Unexpected result {:?}", res),
+    }
+
+    let mut env = Env::new();
+    env.insert("length".to_string(), (vec![], vec![], Ty::Int));
+    env.insert("lengths".to_string(), (vec![], vec![], Ty::Int));
+    env.insert("map".to_string(), (vec![], vec![], Ty::Int));
+    let err = infer_str(&env, "lenght").unwrap_err();
+    assert_eq!(
+        TypeError::Unbound {
+            ident: "lenght".to_string(),
+            suggestions: vec!["length".to_string(), "lengths".to_string()],
+        },
+        err
+    );
+    assert_eq!(
+        "Unbound identifier lenght\n  did you mean length or lengths?",
+        err.to_string()
+    );
+
+    // Neither operators nor other one letter names are suggested for one
+    // letter names, but a single edit is for two letter names
+    env.insert("%".to_string(), (vec![], vec![], Ty::Int));
+    env.insert("f".to_string(), (vec![], vec![], Ty::Int));
+    env.insert("fn".to_string(), (vec![], vec![], Ty::Int));
+    assert_eq!(
+        Err(TypeError::Unbound {
+            ident: "g".to_string(),
+            suggestions: vec![],
+        }),
+        infer_str(&env, "g")
+    );
+    assert_eq!(
+        Err(TypeError::Unbound {
+            ident: "gn".to_string(),
+            suggestions: vec!["fn".to_string()],
+        }),
+        infer_str(&env, "gn")
+    );
+
+    for decl in union_decls("type Seq a = Nil | Cons a (Seq a)") {
+        env.extend(constructor_schemes(&decl));
+    }
+    assert!(matches!(
+        infer_str(&env, "case Nil of Cons x => x end"),
+        Err(TypeError::Arity {
+            expected: 2,
+            actual: 1,
+            ..
+        })
+    ));
+
+    let err = infer_str(&env, "{ x = 1 }.y").unwrap_err();
+    assert!(matches!(
+        &err,
+        TypeError::Fields { missing, extra, .. } if missing == &["y"] && extra.is_empty()
+    ));
+    assert_eq!(
+        "Record fields do not match: expected { y: a, ..b }, but found { x: Int }\n  \
+         missing fields: y\n  \
+         `{ x = 1 }` has type { x: Int }",
+        err.to_string()
+    );
+}
+
+#[test]
+fn infinite_types() {
+    fn infer(input: &str) -> Result<Ty, TypeError> {
+        infer_str(&Env::new(), input)
+    }
+
+    let err = infer("lambda x -> x x").unwrap_err();
+    assert!(matches!(err, TypeError::Occurs { .. }));
+    assert_eq!(
+        "Infinite type: a cannot be equal to a -> b, which contains it\n  \
+         `x` has type a\n  \
+         `x` has type a",
+        err.to_string()
+    );
+
+    assert!(matches!(
+        infer("let rec f = lambda x -> f in f end"),
+        Err(TypeError::Occurs { .. })
+    ));
+
+    assert!(matches!(
+        infer("lambda f -> lambda x -> f (x, f x)"),
+        Err(TypeError::Occurs { .. })
+    ));
+
+    assert!(matches!(
+        infer("lambda l -> l :: l"),
+        Err(TypeError::Occurs { .. })
+    ));
+}
+
+#[test]
+fn pattern_variables() {
+    fn infer(input: &str) -> Result<String, TypeError> {
+        infer_str(&Env::new(), input).map(|ty| format!("{}", ty))
+    }
+
+    // The pattern variable is named like the type variable of the parameter,
+    // but their types are unrelated
+    assert_eq!(
+        Ok("x_0 -> (Int, x_0)".to_string()),
+        infer("lambda x -> (case 1 of x_0 => x_0 end, x)")
+    );
+
+    assert_eq!(
+        Ok("x_0 -> (Int, x_0)".to_string()),
+        infer("lambda x -> (let x_0 = 1 in x_0 end, x)")
+    );
+
+    // Pattern variables of different arms are unrelated
+    assert_eq!(
+        Ok("Int".to_string()),
+        infer("case (1, True) of (x, False) => x | (_, x) => if x then 2 else 3 end")
+    );
+
+    // The alternatives of an or-pattern share their variables
+    assert_eq!(
+        Ok("(Int, Int) -> Int".to_string()),
+        infer("lambda p -> case p of (x, 1) | (1, x) => x end")
+    );
+
+    assert!(infer("lambda p -> case p of (x, 1) | (True, x) => x end").is_err());
+}
+
+#[test]
+fn type_classes() {
+    fn env(decl: &str) -> Env {
+        let a = || Ty::Var("a".to_string());
+        let binop = |res: Ty| {
+            Ty::Fun(
+                Box::new(a()),
+                Box::new(Ty::Fun(Box::new(a()), Box::new(res))),
+            )
+        };
+        let mut env = Env::new();
+        env.insert(
+            "==".to_string(),
+            (
+                vec!["a".to_string()],
+                vec![(Class::Eq, "a".to_string())],
+                binop(Ty::Bool),
+            ),
+        );
+        env.insert(
+            "<".to_string(),
+            (
+                vec!["a".to_string()],
+                vec![(Class::Ord, "a".to_string())],
+                binop(Ty::Bool),
+            ),
+        );
+        env.insert(
+            "+".to_string(),
+            (
+                vec!["a".to_string()],
+                vec![(Class::Num, "a".to_string())],
+                binop(a()),
+            ),
+        );
+        env.insert(
+            "show".to_string(),
+            (
+                vec!["a".to_string()],
+                vec![(Class::Show, "a".to_string())],
+                Ty::Fun(Box::new(a()), Box::new(Ty::String)),
+            ),
+        );
+        for decl in union_decls(decl) {
+            env.extend(constructor_schemes(&decl));
+        }
+        env
+    }
+
+    fn infer(decl: &str, input: &str) -> Result<String, String> {
+        infer_str(&env(decl), input)
+            .map(|ty| format!("{}", ty))
+            .map_err(|e| format!("{}", e))
+    }
+
+    fn infer_let(input: &str) -> Result<String, String> {
+        super::infer_let(
+            &mut HashMap::new(),
+            &mut NameSource::new(),
+            &env("type Opt a = None | Some a"),
+            &HashSet::new(),
+            &Pattern::Ident("f".to_string()),
+            &parse_expr(input),
+        )
+        .map(|schemes| fmt_scheme(&schemes[0].1))
+        .map_err(|e| format!("{}", e))
+    }
+
+    let opt = "type Opt a = None | Some a";
+
+    assert_eq!(Ok("Int".to_string()), infer(opt, "1 + 2"));
+    assert_eq!(Ok("Float".to_string()), infer(opt, "1.5 + 2.0"));
+    assert_eq!(
+        Err(
+            "Type String is not an instance of Num\n  `(+)` has type String -> String -> String"
+                .to_string()
+        ),
+        infer(opt, "\"a\" + \"b\"")
+    );
+
+    // Functions cannot be compared or shown, not even inside other types
+    assert_eq!(Ok("Bool".to_string()), infer(opt, "[1] == [2]"));
+    assert_eq!(Ok("Bool".to_string()), infer(opt, "{ x = 1 } < { x = 2 }"));
+    assert_eq!(
+        Err(
+            "Type a -> a is not an instance of Eq\n  `(==)` has type (a -> a) -> (a -> a) -> Bool"
+                .to_string()
+        ),
+        infer(opt, "(lambda x -> x) == (lambda x -> x)")
+    );
+    assert!(infer(opt, "{ x = lambda y -> y } == { x = lambda y -> y }").is_err());
+    assert!(infer(opt, "show show").is_err());
+
+    // Declared types are instances if their constructor arguments are
+    assert_eq!(Ok("Bool".to_string()), infer(opt, "Some 1 == None"));
+    assert_eq!(Ok("String".to_string()), infer(opt, "show (Some [1])"));
+    assert!(infer(opt, "Some (lambda x -> x) == None").is_err());
+    assert!(infer("type F = F (Int -> Int)", "lambda f -> F f == F f").is_err());
+    assert_eq!(
+        Ok("(Seq Int) -> Bool".to_string()),
+        infer(
+            "type Seq a = Nil | Cons a (Seq a)",
+            "lambda xs -> xs == Cons 1 Nil"
+        )
+    );
+
+    // Constraints on the variables of a let binding are part of its type
+    // scheme, and are checked where it is used
+    assert_eq!(
+        Ok("Num arg_3 => arg_3 -> arg_3".to_string()),
+        infer_let("lambda x -> x + x")
+    );
+    assert_eq!(
+        Ok(
+            "(Eq arg_7, Num arg_7, Show arg_7) => arg_7 -> arg_7 -> (Bool, arg_7, String)"
+                .to_string()
+        ),
+        infer_let("lambda x -> lambda y -> (x == y, x + y, show x)")
+    );
+    assert_eq!(
+        Ok("Ord field_5 => { x: field_5, y: field_5, ..row_7 } -> Bool".to_string()),
+        infer_let("lambda r -> r.x < r.y")
+    );
+    assert_eq!(Ok("x_0 -> Int".to_string()), infer_let("lambda x -> 1 + 2"));
+    assert_eq!(
+        Ok("Bool".to_string()),
+        infer(opt, "let f = lambda x -> x == x in f 1 end")
+    );
+    assert_eq!(
+        Err(
+            "Type a -> Bool is not an instance of Eq\n  `f` has type (a -> Bool) -> Bool"
+                .to_string()
+        ),
+        infer(opt, "let f = lambda x -> x == x in f f end")
+    );
+}
+
+#[test]
+fn type_aliases() {
+    fn ty(input: &str) -> Ty {
+        match crate::parse::parse(&format!("create table t {}", input)).unwrap() {
+            Statement::Create(_, def) => def.ty,
+            stm => panic!("Not a table declaration: {}", stm),
+        }
+    }
+
+    let defs: TypeDefs = vec![
+        ("Email".to_string(), vec![]),
+        ("Pair".to_string(), vec!["a".to_string()]),
+    ]
+    .into_iter()
+    .collect();
+    let mut aliases = Aliases::new();
+    aliases.insert("Email".to_string(), (vec![], Ty::String));
+    aliases.insert("Pair".to_string(), (vec!["a".to_string()], ty("(a, a)")));
+
+    assert_eq!(
+        Ok(ty("{ sender: String, recipients: [String] }")),
+        expand_aliases(&aliases, &ty("{ sender: Email, recipients: [Email] }"))
+    );
+
+    // An argument mentioning the parameter of the alias is substituted once
+    assert_eq!(
+        Ok(ty("((a, a), (a, a))")),
+        expand_aliases(&aliases, &ty("Pair (Pair a)"))
+    );
+
+    assert!(expand_aliases(&aliases, &ty("Pair")).is_err());
+
+    assert_eq!(
+        Ok(ty("(String, String) -> b")),
+        check_alias(
+            &defs,
+            &aliases,
+            "F",
+            &["b".to_string()],
+            &ty("(Pair Email) -> b")
+        )
+    );
+
+    assert!(check_alias(&defs, &aliases, "Email", &[], &ty("Int")).is_err());
+    assert!(check_alias(&defs, &aliases, "Int", &[], &ty("String")).is_err());
+    assert!(check_alias(&defs, &aliases, "F", &[], &ty("F")).is_err());
+    assert!(check_alias(&defs, &aliases, "F", &[], &ty("a")).is_err());
+}
+
+#[test]
+fn infer_scheme() {
+    fn infer(input: &str) -> Result<String, TypeError> {
+        let mut env = Env::new();
+        env.insert(
+            "==".to_string(),
+            (
+                vec!["a".to_string()],
+                vec![(Class::Eq, "a".to_string())],
+                Ty::Fun(
+                    Box::new(Ty::Var("a".to_string())),
+                    Box::new(Ty::Fun(
+                        Box::new(Ty::Var("a".to_string())),
+                        Box::new(Ty::Bool),
+                    )),
+                ),
+            ),
+        );
+
+        super::infer_scheme(
+            &mut HashMap::new(),
+            &mut NameSource::new(),
+            &env,
+            &HashSet::new(),
+            &parse_expr(input),
+        )
+        .map(|scheme| fmt_scheme(&super::rename_scheme(&scheme)))
+    }
+
+    assert_eq!(Ok("Int".to_string()), infer("1"));
+
+    // The variables are named in the order they occur
+    assert_eq!(
+        Ok("(a -> b) -> a -> b".to_string()),
+        infer("lambda f -> lambda x -> f x")
+    );
+
+    assert_eq!(
+        Ok("{ name: a, ..b } -> a".to_string()),
+        infer("lambda r -> r.name")
+    );
+
+    assert_eq!(
+        Ok("Eq b => a -> b -> Bool".to_string()),
+        infer("lambda x -> lambda y -> y == y")
+    );
+
+    assert!(infer("1 == (lambda x -> x)").is_err());
+}
+
+#[test]
+fn rename_scheme() {
+    use Ty::*;
+
+    let scheme = (
+        vec!["b".to_string(), "x_0".to_string(), "a".to_string()],
+        vec![(Class::Ord, "a".to_string())],
+        Tuple(vec![
+            Var("x_0".to_string()),
+            Var("a".to_string()),
+            Var("c".to_string()),
+            Var("b".to_string()),
+        ]),
+    );
+
+    // Renaming `a` to `b` and `b` to `c` happens at once, and the free
+    // variable `c` keeps its name
+    assert_eq!(
+        (
+            vec!["a".to_string(), "b".to_string(), "d".to_string()],
+            vec![(Class::Ord, "b".to_string())],
+            Tuple(vec![
+                Var("a".to_string()),
+                Var("b".to_string()),
+                Var("c".to_string()),
+                Var("d".to_string()),
+            ]),
+        ),
+        super::rename_scheme(&scheme)
+    );
+}
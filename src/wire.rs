@@ -0,0 +1,41 @@
+//! The protocol between the server and pdbcli. Statements and the replies to
+//! them are sent as s-expressions, one per line. Newlines inside a reply are
+//! escaped, so a reply spanning several lines is still read as one.
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+/// The reply to a statement: either its output or the error it failed with
+pub type Reply = std::result::Result<String, String>;
+
+/// Send `value` as a single line
+pub async fn send<T: Serialize>(stream: &mut (impl AsyncWrite + Unpin), value: &T) -> Result<()> {
+    let mut line = serde_lexpr::to_string(value)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes()).await?;
+    stream.flush().await?;
+
+    Ok(())
+}
+
+/// Receive a line sent by `send`, or `None` if the stream has been closed
+pub async fn receive_line(stream: &mut (impl AsyncBufRead + Unpin)) -> Result<Option<String>> {
+    let mut line = String::new();
+    if stream.read_line(&mut line).await? == 0 {
+        Ok(None)
+    } else {
+        Ok(Some(line))
+    }
+}
+
+/// Receive a value sent by `send`, or `None` if the stream has been closed
+pub async fn receive<T: DeserializeOwned>(
+    stream: &mut (impl AsyncBufRead + Unpin),
+) -> Result<Option<T>> {
+    match receive_line(stream).await? {
+        Some(line) => Ok(Some(serde_lexpr::from_str(&line)?)),
+        None => Ok(None),
+    }
+}